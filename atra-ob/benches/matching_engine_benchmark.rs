@@ -1,13 +1,15 @@
 use criterion::{criterion_group, criterion_main, Criterion, BenchmarkId};
-use atra_ob::core::{MatchingEngine, Order, Side, OrderType};
+use atra_ob::core::{InstrumentConfig, MatchingEngine, Order, PriceLadder, Side, OrderType};
 use rust_decimal_macros::dec;
 use rust_decimal::Decimal;
-use std::time::Instant;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
 use std::fs::File;
 
 #[derive(serde::Serialize)]
 struct BenchmarkResults {
     depth_impact: Vec<DepthMeasurement>,
+    cancel_depth: Vec<DepthMeasurement>,
     latency_distribution: Vec<u128>,
     batch_performance: Vec<BatchMeasurement>,
 }
@@ -28,6 +30,7 @@ struct BatchMeasurement {
 fn run_benchmarks(c: &mut Criterion) {
     let mut results = BenchmarkResults {
         depth_impact: Vec::new(),
+        cancel_depth: Vec::new(),
         latency_distribution: Vec::new(),
         batch_performance: Vec::new(),
     };
@@ -50,21 +53,24 @@ fn run_benchmarks(c: &mut Criterion) {
                     dec!(1.0),
                     Side::Bid,
                     OrderType::Limit
-                ));
+                )).unwrap();
             }
 
+            // ids have to stay unique across iterations
+            let mut next_id = size as u64;
             b.iter(|| {
+                next_id += 1;
                 let start = Instant::now();
                 let order = Order::new(
-                    size as u64 + 1,
+                    next_id,
                     1,
-                    size as u64 + 2,
+                    next_id + 1,
                     dec!(100.0),
                     dec!(1.0),
                     Side::Ask,
                     OrderType::Limit
                 );
-                engine.place_order(order);
+                engine.place_order(order).unwrap();
                 latencies.push(start.elapsed().as_nanos());
             });
         });
@@ -80,6 +86,131 @@ fn run_benchmarks(c: &mut Criterion) {
         group.finish();
     }
 
+    // Measure cancel cost against the depth of a single price level
+    for depth in [100, 1000, 10000].iter() {
+        let mut group = c.benchmark_group("cancel_depth");
+        let mut latencies = Vec::new();
+
+        group.bench_with_input(BenchmarkId::new("cancel_mid_level", depth), depth, |b, &size| {
+            let mut engine = MatchingEngine::new();
+            let mut live = VecDeque::new();
+
+            // Pre-fill one level, all at the same price
+            for i in 0..size as u64 {
+                engine.place_order(Order::new(i, 1, i + 1, dec!(100.0), dec!(1.0), Side::Bid, OrderType::Limit)).unwrap();
+                live.push_back(i);
+            }
+
+            // cancel from the middle of the queue, topping the level back up untimed
+            let mut next_id = size as u64;
+            b.iter_custom(|iters| {
+                let mut total = Duration::ZERO;
+                for _ in 0..iters {
+                    let victim = live.remove(live.len() / 2).unwrap();
+                    let start = Instant::now();
+                    engine.cancel_order(victim).unwrap();
+                    let elapsed = start.elapsed();
+                    total += elapsed;
+                    latencies.push(elapsed.as_nanos());
+
+                    engine.place_order(Order::new(next_id, 1, next_id + 1, dec!(100.0), dec!(1.0), Side::Bid, OrderType::Limit)).unwrap();
+                    live.push_back(next_id);
+                    next_id += 1;
+                }
+                total
+            });
+        });
+
+        latencies.sort();
+        results.cancel_depth.push(DepthMeasurement {
+            depth: *depth,
+            latency_ns: latencies[latencies.len() / 2],
+        });
+
+        group.finish();
+    }
+
+    // Take one lot off the front of a single deep level, topping it back up untimed
+    for depth in [100, 1000, 10000].iter() {
+        let mut group = c.benchmark_group("level_depth");
+
+        group.bench_with_input(BenchmarkId::new("take_from_deep_level", depth), depth, |b, &size| {
+            let mut engine = MatchingEngine::new();
+            for i in 0..size as u64 {
+                engine.place_order(Order::new(i, 1, i + 1, dec!(100.0), dec!(1.0), Side::Bid, OrderType::Limit)).unwrap();
+            }
+
+            let mut next_id = size as u64;
+            b.iter_custom(|iters| {
+                let mut total = Duration::ZERO;
+                for _ in 0..iters {
+                    let start = Instant::now();
+                    engine.place_order(Order::new(next_id, 1, next_id + 1, dec!(100.0), dec!(1.0), Side::Ask, OrderType::Limit)).unwrap();
+                    total += start.elapsed();
+
+                    engine.place_order(Order::new(next_id + 1, 1, next_id + 2, dec!(100.0), dec!(1.0), Side::Bid, OrderType::Limit)).unwrap();
+                    next_id += 2;
+                }
+                total
+            });
+        });
+
+        group.finish();
+    }
+
+    // Compare the price ladders on a book a thousand ticks deep each side:
+    // rest a passive order somewhere in it, look at the top, take the order out again
+    let ladders = [
+        ("tree", PriceLadder::Tree),
+        ("dense", PriceLadder::Dense { reference_price: dec!(100.00), ticks: 4096 }),
+    ];
+    for (name, ladder) in ladders {
+        let mut group = c.benchmark_group("orderbook_depth");
+        group.bench_function(name, |b| {
+            let mut engine = ladder_engine(ladder);
+            for i in 0..1000u64 {
+                let offset = Decimal::new(i as i64 + 1, 2);
+                engine.place_order(Order::new(2 * i, 1, 2 * i + 1, dec!(100.00) - offset, dec!(1.0), Side::Bid, OrderType::Limit)).unwrap();
+                engine.place_order(Order::new(2 * i + 1, 1, 2 * i + 2, dec!(100.00) + offset, dec!(1.0), Side::Ask, OrderType::Limit)).unwrap();
+            }
+
+            let mut next_id = 2000u64;
+            b.iter(|| {
+                next_id += 1;
+                let price = dec!(100.00) - Decimal::new((next_id % 1000) as i64 + 1, 2);
+                engine.place_order(Order::new(next_id, 1, next_id, price, dec!(1.0), Side::Bid, OrderType::Limit)).unwrap();
+                criterion::black_box(engine.get_order_book(10));
+                engine.cancel_order(next_id).unwrap();
+            });
+        });
+        group.finish();
+    }
+
+    // Compare the price ladders under flow around the touch that both trades and rests
+    for (name, ladder) in ladders {
+        let mut group = c.benchmark_group("continuous_matching");
+        group.bench_function(name, |b| {
+            let mut engine = ladder_engine(ladder);
+            for i in 0..200u64 {
+                let offset = Decimal::new((i % 20) as i64 + 1, 2);
+                engine.place_order(Order::new(2 * i, 1, 2 * i + 1, dec!(100.00) - offset, dec!(1.0), Side::Bid, OrderType::Limit)).unwrap();
+                engine.place_order(Order::new(2 * i + 1, 1, 2 * i + 2, dec!(100.00) + offset, dec!(1.0), Side::Ask, OrderType::Limit)).unwrap();
+            }
+
+            let mut next_id = 400u64;
+            let mut seed = 1u64;
+            b.iter(|| {
+                next_id += 1;
+                seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+                let side = if seed >> 63 == 0 { Side::Bid } else { Side::Ask };
+                let price = dec!(100.00) + Decimal::new(((seed >> 33) % 21) as i64 - 10, 2);
+                let quantity = Decimal::from((seed >> 40) % 3 + 1);
+                engine.place_order(Order::new(next_id, 1, next_id, price, quantity, side, OrderType::Limit)).unwrap();
+            });
+        });
+        group.finish();
+    }
+
     // Measure latency distribution
     let mut engine = MatchingEngine::new();
     let mut latencies = Vec::new();
@@ -94,7 +225,7 @@ fn run_benchmarks(c: &mut Criterion) {
             dec!(1.0),
             Side::Bid,
             OrderType::Limit
-        ));
+        )).unwrap();
     }
 
     // Collect latencies for 1000 orders
@@ -108,7 +239,7 @@ fn run_benchmarks(c: &mut Criterion) {
             dec!(1.0),
             Side::Ask,
             OrderType::Limit
-        ));
+        )).unwrap();
         latencies.push(start.elapsed().as_nanos());
     }
     results.latency_distribution = latencies;
@@ -127,7 +258,7 @@ fn run_benchmarks(c: &mut Criterion) {
                 dec!(1.0),
                 if i % 2 == 0 { Side::Bid } else { Side::Ask },
                 OrderType::Limit
-            ));
+            )).unwrap();
         }
 
         let elapsed = start.elapsed();
@@ -143,5 +274,14 @@ fn run_benchmarks(c: &mut Criterion) {
     serde_json::to_writer_pretty(file, &results).unwrap();
 }
 
+fn ladder_engine(ladder: PriceLadder) -> MatchingEngine {
+    MatchingEngine::with_config(InstrumentConfig {
+        tick_size: dec!(0.01),
+        price_ladder: ladder,
+        ..Default::default()
+    })
+    .unwrap()
+}
+
 criterion_group!(benches, run_benchmarks);
 criterion_main!(benches);
@@ -1,25 +1,31 @@
-use crate::core::MatchingEngine;
-use crate::core::{Order, OrderType, Side};
+#![allow(clippy::result_large_err)] // tonic::Status is the error type throughout
+
+use crate::core::{Command, EngineError, EngineEvent, Fill, InstrumentConfig, DEFAULT_SCALE, Liquidity, MatchingEngine, Uncross};
+use crate::core::{GroupKind, GroupRole, Order, OrderType, Peg, PegType, PostOnly, Side, StatusReason, StpMode, TimeInForce, Trade, Trail, TradingPhase};
 use crate::proto;
 use crate::proto::order_book_service_server::{
     OrderBookService as GrpcService, OrderBookServiceServer,
 };
 use crate::proto::{
-    BatchMode, CancelOrderBatchRequest, CancelOrderBatchResponse, CancelOrderRequest, DecimalValue, ErrorCode,
-    ErrorDetail, GetOrderBookRequest, GetOrderStatusRequest, GetTradeHistoryRequest, OrderBatchItemResult,
-    OrderBatchRequest, OrderBatchResponse, OrderRequest, OrderResponse, Side as ProtoSide,
+    AmendOrderRequest, AuctionStateResponse, BatchMode, CancelOrderBatchRequest, CancelOrderBatchResponse, CancelOrderRequest, DecimalValue, ErrorCode,
+    ErrorDetail, GetAuctionStateRequest, GetOrderBookRequest, GetOrderStatusRequest, GetTradeHistoryRequest, GetTradeRequest, OrderBatchItemResult,
+    OrderBatchRequest, OrderBatchResponse, OrderGroupRequest, OrderGroupResponse, OrderRequest, OrderResponse, SetTradingPhaseRequest, Side as ProtoSide,
     StreamOrderBookRequest, StreamTradeHistoryRequest, Trade as ProtoTrade, TradeHistoryResponse,
 };
+use chrono::{DateTime, Utc};
 use prost_types::Timestamp;
 use rust_decimal::Decimal;
+use std::collections::hash_map::Entry;
 use std::collections::{HashMap, HashSet};
 use std::pin::Pin;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
 use futures::Stream;
-use tokio::sync::{mpsc, oneshot, Mutex, RwLock};
-use tonic::{transport::Server, Request, Response, Status};
+use prost::Message;
+use tokio::time::Instant;
+use tokio::sync::{broadcast, mpsc, oneshot, Mutex, RwLock};
+use tonic::{codegen::Bytes, transport::Server, Request, Response, Status};
 
 #[derive(Clone, Copy)]
 pub struct SequencerConfig {
@@ -52,8 +58,8 @@ impl InstrumentState {
 
 enum WorkerCommand {
     Place {
-        order: Order,
-        response: oneshot::Sender<Result<Order, Status>>,
+        order: Box<Order>,
+        response: oneshot::Sender<Result<(Order, Vec<Fill>), Status>>, // with the fills the call produced
     },
     Cancel {
         order_id: u64,
@@ -61,6 +67,21 @@ enum WorkerCommand {
         idempotency_key: Option<String>,
         response: oneshot::Sender<Result<Order, Status>>,
     },
+    PlaceGroup {
+        group_id: u64,
+        kind: GroupKind,
+        orders: Vec<Order>, // BRACKET: parent, take-profit, stop-loss
+        response: oneshot::Sender<Result<Vec<Order>, Status>>,
+    },
+    Amend {
+        order_id: u64,
+        instrument_id: u32,
+        price: Option<Decimal>,
+        quantity: Option<Decimal>,
+        sequencer: Arc<InstrumentState>, // draws a sequence only if the amend re-queues the order
+        idempotency_key: Option<String>,
+        response: oneshot::Sender<Result<Order, Status>>,
+    },
     Snapshot {
         depth: usize,
         instrument_id: u32,
@@ -76,14 +97,36 @@ enum WorkerCommand {
         instrument_id: u32,
         response: oneshot::Sender<Result<Vec<ProtoTrade>, Status>>,
     },
+    Trade {
+        trade_id: u64,
+        instrument_id: u32,
+        response: oneshot::Sender<Result<ProtoTrade, Status>>,
+    },
+    SetPhase {
+        instrument_id: u32,
+        phase: TradingPhase,
+        response: oneshot::Sender<Result<AuctionStateResponse, Status>>,
+    },
+    AuctionState {
+        instrument_id: u32,
+        response: oneshot::Sender<Result<AuctionStateResponse, Status>>,
+    },
 }
 
+/// an engine event and the instrument whose engine it came from
+pub type InstrumentEvent = (u32, EngineEvent);
+
+/// how far an event subscriber may fall behind before it starts missing events
+const EVENT_BUFFER: usize = 4096;
+
 #[derive(Clone)]
 pub struct OrderBookService {
     lanes: Arc<RwLock<HashMap<u32, mpsc::Sender<WorkerCommand>>>>,
     lane_states: Arc<RwLock<HashMap<u32, Arc<InstrumentState>>>>,
     lane_count: u32,
     config: SequencerConfig,
+    instrument_configs: Arc<HashMap<u32, InstrumentConfig>>,
+    events: broadcast::Sender<InstrumentEvent>, // everything every lane's engines do
 }
 
 impl OrderBookService {
@@ -93,9 +136,26 @@ impl OrderBookService {
             lane_states: Arc::new(RwLock::new(HashMap::new())),
             lane_count: lane_count.max(1),
             config,
+            instrument_configs: Arc::new(HashMap::new()),
+            events: broadcast::channel(EVENT_BUFFER).0,
         }
     }
 
+    /// every event the engines emit from here on, including the ones no request waits
+    /// for: expiries, batch clears and what they set off. each instrument's events come
+    /// in the order its engine emitted them; a subscriber that falls more than
+    /// `EVENT_BUFFER` behind is told how many it missed and carries on from there.
+    pub fn subscribe_events(&self) -> broadcast::Receiver<InstrumentEvent> {
+        self.events.subscribe()
+    }
+
+    /// override the engine settings for one instrument; the rest use the defaults.
+    /// has to happen before the instrument's lane starts.
+    pub fn with_instrument_config(mut self, instrument_id: u32, config: InstrumentConfig) -> Self {
+        Arc::make_mut(&mut self.instrument_configs).insert(instrument_id, config);
+        self
+    }
+
     pub async fn serve(self, addr: &str) -> Result<(), Box<dyn std::error::Error>> {
         let addr = addr.parse()?;
         Server::builder()
@@ -121,7 +181,7 @@ impl OrderBookService {
         }
 
         let (tx, rx) = mpsc::channel(4096);
-        tokio::spawn(run_lane_worker(rx));
+        tokio::spawn(run_lane_worker(rx, self.instrument_configs.clone(), self.events.clone()));
         lanes.insert(lane_id, tx.clone());
         tx
     }
@@ -169,8 +229,143 @@ impl OrderBookService {
         let order_type = match proto::OrderType::try_from(req.order_type) {
             Ok(proto::OrderType::Limit) => OrderType::Limit,
             Ok(proto::OrderType::Market) => OrderType::Market,
+            Ok(proto::OrderType::MarketToLimit) => OrderType::MarketToLimit,
+            Ok(proto::OrderType::Stop) => OrderType::Stop,
+            Ok(proto::OrderType::StopLimit) => OrderType::StopLimit,
             _ => return Err(Status::invalid_argument("Invalid order_type")),
         };
+        let stop_price = match order_type {
+            OrderType::Stop | OrderType::StopLimit => {
+                Some(decimal_from_proto(req.stop_price.as_ref(), "stop_price")?)
+            }
+            OrderType::Limit | OrderType::Market | OrderType::MarketToLimit => None,
+        };
+        let protection_price = match req.protection_price.as_ref() {
+            Some(value) => Some(decimal_from_proto(Some(value), "protection_price")?),
+            None => None,
+        };
+        if protection_price.is_some()
+            && !matches!(order_type, OrderType::Market | OrderType::MarketToLimit | OrderType::Stop)
+        {
+            return Err(Status::invalid_argument("protection_price requires a market-type order"));
+        }
+        let trail = match (req.trail_amount.as_ref(), req.trail_percent.as_ref()) {
+            (None, None) => None,
+            (Some(value), None) => Some(Trail::Amount(decimal_from_proto(Some(value), "trail_amount")?)),
+            (None, Some(value)) => Some(Trail::Percent(decimal_from_proto(Some(value), "trail_percent")?)),
+            (Some(_), Some(_)) => {
+                return Err(Status::invalid_argument("set only one of trail_amount and trail_percent"))
+            }
+        };
+        if let Some(trail) = trail {
+            if stop_price.is_none() {
+                return Err(Status::invalid_argument("trailing requires a stop order"));
+            }
+            let (Trail::Amount(value) | Trail::Percent(value)) = trail;
+            if value <= Decimal::ZERO {
+                return Err(Status::invalid_argument("trail must be positive"));
+            }
+        }
+        let time_in_force = match proto::TimeInForce::try_from(req.time_in_force) {
+            Ok(proto::TimeInForce::Unspecified) | Ok(proto::TimeInForce::Gtc) => TimeInForce::Gtc,
+            Ok(proto::TimeInForce::Ioc) => TimeInForce::Ioc,
+            Ok(proto::TimeInForce::Fok) => TimeInForce::Fok,
+            Ok(proto::TimeInForce::Gtd) => TimeInForce::Gtd,
+            Ok(proto::TimeInForce::Day) => TimeInForce::Day,
+            Err(_) => return Err(Status::invalid_argument("Invalid time_in_force")),
+        };
+        let display_quantity = match req.display_quantity.as_ref() {
+            Some(value) => Some(decimal_from_proto(Some(value), "display_quantity")?),
+            None => None,
+        };
+        if let Some(display) = display_quantity {
+            if display <= Decimal::ZERO {
+                return Err(Status::invalid_argument("display_quantity must be positive"));
+            }
+            if !matches!(order_type, OrderType::Limit | OrderType::StopLimit) {
+                return Err(Status::invalid_argument("display_quantity requires a limit order"));
+            }
+        }
+        let min_quantity = match req.min_quantity.as_ref() {
+            Some(value) => Some(decimal_from_proto(Some(value), "min_quantity")?),
+            None => None,
+        };
+        if let Some(min_quantity) = min_quantity {
+            if min_quantity <= Decimal::ZERO || min_quantity > quantity {
+                return Err(Status::invalid_argument("min_quantity must be positive and at most quantity"));
+            }
+        }
+        if req.all_or_none && display_quantity.is_some() {
+            return Err(Status::invalid_argument("all_or_none can't be combined with display_quantity"));
+        }
+        if req.hidden && (order_type != OrderType::Limit || display_quantity.is_some()) {
+            return Err(Status::invalid_argument("hidden requires a limit order without display_quantity"));
+        }
+        if req.midpoint {
+            if !matches!(order_type, OrderType::Limit | OrderType::Market) {
+                return Err(Status::invalid_argument("midpoint requires a limit or market order"));
+            }
+            if req.hidden || display_quantity.is_some() || req.post_only != 0 || req.peg_type != 0 {
+                return Err(Status::invalid_argument("midpoint orders can't be hidden, iceberg, post-only or pegged"));
+            }
+            if req.all_or_none || min_quantity.is_some() || req.stp_mode != 0 {
+                return Err(Status::invalid_argument("midpoint orders can't be all-or-none or carry min_quantity or stp_mode"));
+            }
+        }
+        let post_only = match proto::PostOnly::try_from(req.post_only) {
+            Ok(proto::PostOnly::Unspecified) => None,
+            Ok(proto::PostOnly::Reject) => Some(PostOnly::Reject),
+            Ok(proto::PostOnly::Reprice) => Some(PostOnly::Reprice),
+            Err(_) => return Err(Status::invalid_argument("Invalid post_only")),
+        };
+        if post_only.is_some() && order_type != OrderType::Limit {
+            return Err(Status::invalid_argument("post_only requires a limit order"));
+        }
+        let stp_mode = match proto::StpMode::try_from(req.stp_mode) {
+            Ok(proto::StpMode::Unspecified) => None,
+            Ok(proto::StpMode::CancelNewest) => Some(StpMode::CancelNewest),
+            Ok(proto::StpMode::CancelOldest) => Some(StpMode::CancelOldest),
+            Ok(proto::StpMode::CancelBoth) => Some(StpMode::CancelBoth),
+            Ok(proto::StpMode::DecrementAndCancel) => Some(StpMode::DecrementAndCancel),
+            Err(_) => return Err(Status::invalid_argument("Invalid stp_mode")),
+        };
+        if stp_mode.is_some() && req.owner_id.is_none() {
+            return Err(Status::invalid_argument("stp_mode requires owner_id"));
+        }
+        let peg_type = match proto::PegType::try_from(req.peg_type) {
+            Ok(proto::PegType::Unspecified) => None,
+            Ok(proto::PegType::Primary) => Some(PegType::Primary),
+            Ok(proto::PegType::Market) => Some(PegType::Market),
+            Ok(proto::PegType::Midpoint) => Some(PegType::Midpoint),
+            Err(_) => return Err(Status::invalid_argument("Invalid peg_type")),
+        };
+        let peg = match peg_type {
+            Some(peg_type) => {
+                if order_type != OrderType::Limit {
+                    return Err(Status::invalid_argument("peg_type requires a limit order"));
+                }
+                let offset = match req.peg_offset.as_ref() {
+                    Some(value) => decimal_from_proto(Some(value), "peg_offset")?,
+                    None => Decimal::ZERO,
+                };
+                let limit_price = match req.peg_limit_price.as_ref() {
+                    Some(value) => Some(decimal_from_proto(Some(value), "peg_limit_price")?),
+                    None => None,
+                };
+                Some(Peg { peg_type, offset, limit_price })
+            }
+            None => None,
+        };
+        let expire_time = req.expire_time.as_ref().map(timestamp_from_proto).transpose()?;
+        if time_in_force == TimeInForce::Gtd {
+            match expire_time {
+                None => return Err(Status::invalid_argument("GTD order requires expire_time")),
+                Some(ts) if ts <= Utc::now() => {
+                    return Err(Status::invalid_argument("expire_time is in the past"))
+                }
+                _ => {}
+            }
+        }
 
         let mut order = Order::new(
             req.id,
@@ -183,6 +378,20 @@ impl OrderBookService {
         );
         order.ingress_timestamp_ns = req.ingress_timestamp_ns;
         order.idempotency_key = req.idempotency_key;
+        order.time_in_force = time_in_force;
+        order.expire_time = expire_time;
+        order.post_only = post_only;
+        order.stop_price = stop_price;
+        order.trail = trail;
+        order.protection_price = protection_price;
+        order.min_quantity = min_quantity;
+        order.all_or_none = req.all_or_none;
+        order.hidden = req.hidden;
+        order.midpoint = req.midpoint;
+        order.display_quantity = display_quantity;
+        order.owner_id = req.owner_id;
+        order.stp_mode = stp_mode;
+        order.peg = peg;
         Ok(order)
     }
 }
@@ -206,7 +415,27 @@ fn decimal_to_proto(value: Decimal) -> DecimalValue {
     }
 }
 
+fn timestamp_from_proto(value: &Timestamp) -> Result<DateTime<Utc>, Status> {
+    let nanos = u32::try_from(value.nanos)
+        .map_err(|_| Status::invalid_argument(format!("Invalid timestamp nanos: {}", value.nanos)))?;
+    DateTime::from_timestamp(value.seconds, nanos)
+        .ok_or_else(|| Status::invalid_argument(format!("Invalid timestamp: {}s", value.seconds)))
+}
+
+fn timestamp_to_proto(ts: DateTime<Utc>) -> Timestamp {
+    Timestamp {
+        seconds: ts.timestamp(),
+        nanos: ts.timestamp_subsec_nanos() as i32,
+    }
+}
+
 fn status_from_error(err: &Status) -> ErrorDetail {
+    // engine errors carry their own detail, keep the specific code
+    if let Ok(detail) = ErrorDetail::decode(err.details()) {
+        if detail.code != ErrorCode::Unspecified as i32 {
+            return detail;
+        }
+    }
     let code = match err.code() {
         tonic::Code::InvalidArgument => ErrorCode::InvalidArgument,
         tonic::Code::NotFound => ErrorCode::NotFound,
@@ -220,6 +449,39 @@ fn status_from_error(err: &Status) -> ErrorDetail {
     }
 }
 
+fn status_from_engine_error(err: EngineError) -> Status {
+    let (code, detail) = match &err {
+        EngineError::DuplicateOrderId(_) => (tonic::Code::AlreadyExists, ErrorCode::DuplicateOrderId),
+        EngineError::UnknownOrder(_) => (tonic::Code::NotFound, ErrorCode::UnknownOrder),
+        EngineError::OrderClosed { .. } => (tonic::Code::FailedPrecondition, ErrorCode::OrderClosed),
+        EngineError::NotAmendable(_) => (tonic::Code::FailedPrecondition, ErrorCode::NotAmendable),
+        EngineError::InvalidPrice(_) => (tonic::Code::InvalidArgument, ErrorCode::InvalidPrice),
+        EngineError::InvalidQuantity(_) => (tonic::Code::InvalidArgument, ErrorCode::InvalidQuantity),
+        EngineError::InstrumentHalted(_) => (tonic::Code::FailedPrecondition, ErrorCode::InstrumentHalted),
+        EngineError::RiskRejected(_) => (tonic::Code::FailedPrecondition, ErrorCode::RiskRejected),
+        EngineError::DuplicateGroupId(_) => (tonic::Code::AlreadyExists, ErrorCode::DuplicateGroupId),
+        EngineError::UnknownTrade(_) => (tonic::Code::NotFound, ErrorCode::UnknownTrade),
+        EngineError::UnsupportedOrder(_) => (tonic::Code::InvalidArgument, ErrorCode::InvalidArgument),
+        EngineError::InvalidConfig(_) => (tonic::Code::Internal, ErrorCode::Internal),
+    };
+    let message = err.to_string();
+    let details = ErrorDetail {
+        code: detail as i32,
+        message: message.clone(),
+    };
+    Status::with_details(code, message, Bytes::from(details.encode_to_vec()))
+}
+
+fn stp_mode_to_proto(mode: Option<StpMode>) -> i32 {
+    match mode {
+        None => proto::StpMode::Unspecified as i32,
+        Some(StpMode::CancelNewest) => proto::StpMode::CancelNewest as i32,
+        Some(StpMode::CancelOldest) => proto::StpMode::CancelOldest as i32,
+        Some(StpMode::CancelBoth) => proto::StpMode::CancelBoth as i32,
+        Some(StpMode::DecrementAndCancel) => proto::StpMode::DecrementAndCancel as i32,
+    }
+}
+
 fn order_to_response(result: Order) -> OrderResponse {
     let side = match result.side {
         Side::Bid => ProtoSide::Bid as i32,
@@ -228,14 +490,55 @@ fn order_to_response(result: Order) -> OrderResponse {
     let order_type = match result.order_type {
         OrderType::Limit => proto::OrderType::Limit as i32,
         OrderType::Market => proto::OrderType::Market as i32,
+        OrderType::MarketToLimit => proto::OrderType::MarketToLimit as i32,
+        OrderType::Stop => proto::OrderType::Stop as i32,
+        OrderType::StopLimit => proto::OrderType::StopLimit as i32,
     };
     let status = match result.status {
         crate::core::OrderStatus::Pending => proto::OrderStatus::Pending as i32,
         crate::core::OrderStatus::PartiallyFilled => proto::OrderStatus::PartiallyFilled as i32,
         crate::core::OrderStatus::Filled => proto::OrderStatus::Filled as i32,
         crate::core::OrderStatus::Cancelled => proto::OrderStatus::Cancelled as i32,
+        crate::core::OrderStatus::Rejected => proto::OrderStatus::Rejected as i32,
+        crate::core::OrderStatus::Expired => proto::OrderStatus::Expired as i32,
+    };
+    let status_reason = match result.status_reason {
+        None => proto::StatusReason::Unspecified as i32,
+        Some(StatusReason::PostOnlyWouldTake) => proto::StatusReason::PostOnlyWouldTake as i32,
+        Some(StatusReason::FokUnfillable) => proto::StatusReason::FokUnfillable as i32,
+        Some(StatusReason::MinQuantityUnfillable) => proto::StatusReason::MinQuantityUnfillable as i32,
+        Some(StatusReason::NotAcceptedInPhase) => proto::StatusReason::NotAcceptedInPhase as i32,
+        Some(StatusReason::NoPegReference) => proto::StatusReason::NoPegReference as i32,
+        Some(StatusReason::MissingStopPrice) => proto::StatusReason::MissingStopPrice as i32,
+        Some(StatusReason::CancelRequested) => proto::StatusReason::CancelRequested as i32,
+        Some(StatusReason::ImmediateOrCancel) => proto::StatusReason::ImmediateOrCancel as i32,
+        Some(StatusReason::NoLiquidity) => proto::StatusReason::NoLiquidity as i32,
+        Some(StatusReason::ProtectionPrice) => proto::StatusReason::ProtectionPrice as i32,
+        Some(StatusReason::SelfTradePrevention) => proto::StatusReason::SelfTradePrevention as i32,
+        Some(StatusReason::GroupResolved) => proto::StatusReason::GroupResolved as i32,
+        Some(StatusReason::ExpireTime) => proto::StatusReason::ExpireTime as i32,
+    };
+    let time_in_force = match result.time_in_force {
+        TimeInForce::Gtc => proto::TimeInForce::Gtc as i32,
+        TimeInForce::Ioc => proto::TimeInForce::Ioc as i32,
+        TimeInForce::Fok => proto::TimeInForce::Fok as i32,
+        TimeInForce::Gtd => proto::TimeInForce::Gtd as i32,
+        TimeInForce::Day => proto::TimeInForce::Day as i32,
+    };
+    let post_only = match result.post_only {
+        None => proto::PostOnly::Unspecified as i32,
+        Some(PostOnly::Reject) => proto::PostOnly::Reject as i32,
+        Some(PostOnly::Reprice) => proto::PostOnly::Reprice as i32,
+    };
+    let peg_type = match result.peg.map(|peg| peg.peg_type) {
+        None => proto::PegType::Unspecified as i32,
+        Some(PegType::Primary) => proto::PegType::Primary as i32,
+        Some(PegType::Market) => proto::PegType::Market as i32,
+        Some(PegType::Midpoint) => proto::PegType::Midpoint as i32,
     };
 
+    let filled_quantity = result.quantity - result.remaining_quantity;
+
     OrderResponse {
         id: result.id,
         price: Some(decimal_to_proto(result.price)),
@@ -244,22 +547,275 @@ fn order_to_response(result: Order) -> OrderResponse {
         side,
         order_type,
         status,
-        timestamp: result.timestamp.map(|ts| Timestamp {
-            seconds: ts.timestamp(),
-            nanos: ts.timestamp_subsec_nanos() as i32,
-        }),
+        timestamp: result.timestamp.map(timestamp_to_proto),
         instrument_id: result.instrument_id,
         sequence_number: result.sequence,
         ingress_timestamp_ns: result.ingress_timestamp_ns,
         idempotency_key: result.idempotency_key,
+        time_in_force,
+        expire_time: result.expire_time.map(timestamp_to_proto),
+        post_only,
+        stop_price: result.stop_price.map(decimal_to_proto),
+        display_quantity: result.display_quantity.map(decimal_to_proto),
+        owner_id: result.owner_id,
+        stp_mode: stp_mode_to_proto(result.stp_mode),
+        stp_events: result
+            .stp_events
+            .into_iter()
+            .map(|event| proto::StpEvent {
+                resting_order_id: event.resting_order_id,
+                mode: stp_mode_to_proto(Some(event.mode)),
+                taker_quantity_cancelled: Some(decimal_to_proto(event.taker_quantity_cancelled)),
+                resting_quantity_cancelled: Some(decimal_to_proto(event.resting_quantity_cancelled)),
+            })
+            .collect(),
+        peg_type,
+        peg_offset: result.peg.map(|peg| decimal_to_proto(peg.offset)),
+        peg_limit_price: result.peg.and_then(|peg| peg.limit_price).map(decimal_to_proto),
+        trail_amount: match result.trail {
+            Some(Trail::Amount(amount)) => Some(decimal_to_proto(amount)),
+            _ => None,
+        },
+        trail_percent: match result.trail {
+            Some(Trail::Percent(percent)) => Some(decimal_to_proto(percent)),
+            _ => None,
+        },
+        protection_price: result.protection_price.map(decimal_to_proto),
+        min_quantity: result.min_quantity.map(decimal_to_proto),
+        all_or_none: result.all_or_none,
+        group: result.group.map(|membership| proto::OrderGroup {
+            group_id: membership.group_id,
+            group_type: match membership.kind {
+                GroupKind::Oco => proto::OrderGroupType::Oco as i32,
+                GroupKind::Bracket => proto::OrderGroupType::Bracket as i32,
+            },
+            role: match membership.role {
+                GroupRole::Leg => proto::OrderGroupRole::Leg as i32,
+                GroupRole::Parent => proto::OrderGroupRole::Parent as i32,
+                GroupRole::TakeProfit => proto::OrderGroupRole::TakeProfit as i32,
+                GroupRole::StopLoss => proto::OrderGroupRole::StopLoss as i32,
+            },
+        }),
+        hidden: result.hidden,
+        midpoint: result.midpoint,
+        status_reason,
+        fills: Vec::new(),
+        filled_quantity: Some(decimal_to_proto(filled_quantity)),
+        average_fill_price: None,
+    }
+}
+
+/// a placed order's response, carrying the fills the placing call produced
+fn placed_to_response(result: Order, fills: Vec<Fill>, price_scale: u32) -> OrderResponse {
+    let quantity: Decimal = fills.iter().map(|fill| fill.quantity).sum();
+    let notional: Decimal = fills.iter().map(|fill| fill.price * fill.quantity).sum();
+    let mut response = order_to_response(result);
+    if !quantity.is_zero() {
+        // rounded to the instrument's price scale; a DecimalValue can't carry a full 28 places
+        response.average_fill_price = Some(decimal_to_proto((notional / quantity).round_dp(price_scale).normalize()));
+    }
+    response.fills = fills.into_iter().map(fill_to_proto).collect();
+    response
+}
+
+fn fill_to_proto(fill: Fill) -> proto::Fill {
+    proto::Fill {
+        trade_id: fill.trade_id,
+        execution_id: fill.execution_id,
+        price: Some(decimal_to_proto(fill.price)),
+        quantity: Some(decimal_to_proto(fill.quantity)),
+        maker_order_id: fill.maker_order_id(),
+        liquidity: match fill.liquidity {
+            Liquidity::Maker => proto::Liquidity::Maker as i32,
+            Liquidity::Taker => proto::Liquidity::Taker as i32,
+        },
+    }
+}
+
+fn trade_to_proto(trade: &Trade) -> ProtoTrade {
+    ProtoTrade {
+        maker_order_id: trade.maker_order_id,
+        taker_order_id: trade.taker_order_id,
+        price: Some(decimal_to_proto(trade.price)),
+        quantity: Some(decimal_to_proto(trade.quantity)),
+        side: match trade.side {
+            Side::Bid => ProtoSide::Bid as i32,
+            Side::Ask => ProtoSide::Ask as i32,
+        },
+        timestamp: trade.timestamp.map(timestamp_to_proto),
+        maker_sequence_number: trade.maker_sequence,
+        taker_sequence_number: trade.taker_sequence,
+        ingress_timestamp_ns: trade.ingress_timestamp_ns,
+        trade_id: trade.trade_id,
+        maker_execution_id: trade.maker_execution_id,
+        taker_execution_id: trade.taker_execution_id,
+    }
+}
+
+/// what a place command left the order as, with every fill it got along the way
+fn placed_with_fills(engine: &MatchingEngine, order_id: u64, events: Vec<EngineEvent>) -> Result<(Order, Vec<Fill>), Status> {
+    let mut fills = Vec::new();
+    for event in events {
+        match event {
+            EngineEvent::CommandRejected { error, .. } => return Err(status_from_engine_error(error)),
+            EngineEvent::Fill(fill) if fill.order_id == order_id => fills.push(fill),
+            _ => {}
+        }
+    }
+    Ok((order_after(engine, order_id)?, fills))
+}
+
+/// the status a command was refused with, if it was
+fn refusal(events: &[EngineEvent]) -> Result<(), Status> {
+    match events.iter().find(|event| matches!(event, EngineEvent::CommandRejected { .. })) {
+        Some(EngineEvent::CommandRejected { error, .. }) => Err(status_from_engine_error(error.clone())),
+        _ => Ok(()),
+    }
+}
+
+/// an order as the last command left it
+fn order_after(engine: &MatchingEngine, order_id: u64) -> Result<Order, Status> {
+    engine
+        .get_order_status(order_id)
+        .ok_or_else(|| status_from_engine_error(EngineError::UnknownOrder(order_id)))
+}
+
+/// the uncross a command executed, if any
+fn uncrossed(events: &[EngineEvent]) -> Option<Uncross> {
+    events.iter().find_map(|event| match event {
+        EngineEvent::Uncrossed(uncross) => Some(*uncross),
+        _ => None,
+    })
+}
+
+/// apply `command` and pass what it did on to event subscribers, if there are any
+fn apply(
+    engine: &mut MatchingEngine,
+    instrument_id: u32,
+    command: Command,
+    events_tx: &broadcast::Sender<InstrumentEvent>,
+) -> Vec<EngineEvent> {
+    let events = engine.apply(command);
+    if events_tx.receiver_count() > 0 {
+        for event in &events {
+            let _ = events_tx.send((instrument_id, event.clone()));
+        }
+    }
+    events
+}
+
+/// the instrument's engine, created on first use; fails if its config is unusable
+fn engine_for<'a>(
+    engines: &'a mut HashMap<u32, MatchingEngine>,
+    instrument_configs: &HashMap<u32, InstrumentConfig>,
+    batch_deadlines: &mut HashMap<u32, Instant>,
+    instrument_id: u32,
+) -> Result<&'a mut MatchingEngine, Status> {
+    match engines.entry(instrument_id) {
+        Entry::Occupied(entry) => Ok(entry.into_mut()),
+        Entry::Vacant(entry) => {
+            let config = instrument_configs.get(&instrument_id).cloned().unwrap_or_default();
+            let batch_interval = config.batch_interval;
+            let engine = MatchingEngine::with_config(config).map_err(status_from_engine_error)?;
+            if let Some(interval) = batch_interval {
+                batch_deadlines.insert(instrument_id, Instant::now() + interval);
+            }
+            Ok(entry.insert(engine))
+        }
+    }
+}
+
+/// resolves at `deadline`, or never if there isn't one
+async fn sleep_until(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline).await,
+        None => std::future::pending().await,
+    }
+}
+
+/// the point on the monotonic clock a wall-clock time falls at, or now if it's passed
+fn instant_at(time: DateTime<Utc>) -> Instant {
+    Instant::now() + (time - Utc::now()).to_std().unwrap_or_default()
+}
+
+fn auction_state_to_response(
+    instrument_id: u32,
+    engine: Option<&MatchingEngine>,
+    uncross: Option<Uncross>,
+) -> AuctionStateResponse {
+    let phase = engine.map(|e| e.trading_phase()).unwrap_or_default();
+    let indicative = match phase {
+        TradingPhase::Auction | TradingPhase::Batch => engine.and_then(|e| e.indicative_uncross()),
+        TradingPhase::Continuous | TradingPhase::Halted => None,
+    };
+    AuctionStateResponse {
+        instrument_id,
+        phase: match phase {
+            TradingPhase::Continuous => proto::TradingPhase::Continuous as i32,
+            TradingPhase::Auction => proto::TradingPhase::Auction as i32,
+            TradingPhase::Batch => proto::TradingPhase::Batch as i32,
+            TradingPhase::Halted => proto::TradingPhase::Halted as i32,
+        },
+        indicative_price: indicative.map(|u| decimal_to_proto(u.price)),
+        indicative_volume: indicative.map(|u| decimal_to_proto(u.volume)),
+        imbalance: indicative.map(|u| decimal_to_proto(u.imbalance)),
+        uncross_price: uncross.map(|u| decimal_to_proto(u.price)),
+        uncross_volume: uncross.map(|u| decimal_to_proto(u.volume)),
     }
 }
 
-async fn run_lane_worker(mut rx: mpsc::Receiver<WorkerCommand>) {
+async fn run_lane_worker(
+    mut rx: mpsc::Receiver<WorkerCommand>,
+    instrument_configs: Arc<HashMap<u32, InstrumentConfig>>,
+    events_tx: broadcast::Sender<InstrumentEvent>,
+) {
     let engines: Arc<Mutex<HashMap<u32, MatchingEngine>>> = Arc::new(Mutex::new(HashMap::new()));
     let mut seen_idempotency: HashSet<String> = HashSet::new();
     let mut cancel_idempotency_results: HashMap<String, Order> = HashMap::new();
-    while let Some(cmd) = rx.recv().await {
+    let mut amend_idempotency_results: HashMap<String, Order> = HashMap::new();
+    // next clear for each frequent-batch-auction instrument on this lane
+    let mut batch_deadlines: HashMap<u32, Instant> = HashMap::new();
+    loop {
+        let next_batch = batch_deadlines.values().min().copied();
+        let next_expiry = engines.lock().await.values().filter_map(MatchingEngine::next_expiry).min();
+        let cmd = tokio::select! {
+            cmd = rx.recv() => match cmd {
+                Some(cmd) => Some(cmd),
+                None => break,
+            },
+            _ = sleep_until(next_batch) => None,
+            _ = sleep_until(next_expiry.map(instant_at)) => None,
+        };
+
+        // GTD/DAY expiry runs off the wall clock, on its own deadline and again before
+        // every command, so nothing below can observe or match against an order that has
+        // already expired. engines with nothing due are left alone.
+        let now = Utc::now();
+        for (instrument_id, engine) in engines.lock().await.iter_mut() {
+            if engine.next_expiry().is_some_and(|expiry| expiry <= now) {
+                apply(engine, *instrument_id, Command::ExpireOrders { now }, &events_tx);
+            }
+        }
+
+        let tick = Instant::now();
+        for (instrument_id, deadline) in batch_deadlines.iter_mut() {
+            if *deadline > tick {
+                continue;
+            }
+            if let Some(engine) = engines.lock().await.get_mut(instrument_id) {
+                apply(engine, *instrument_id, Command::ClearBatch, &events_tx);
+            }
+            let interval = instrument_configs
+                .get(instrument_id)
+                .and_then(|config| config.batch_interval)
+                .unwrap_or_default();
+            // schedule off the old deadline so intervals don't drift, but never into the past
+            *deadline = (*deadline + interval).max(tick);
+        }
+
+        let Some(cmd) = cmd else {
+            continue;
+        };
         match cmd {
             WorkerCommand::Place { order, response } => {
                 if let Some(key) = &order.idempotency_key {
@@ -270,11 +826,16 @@ async fn run_lane_worker(mut rx: mpsc::Receiver<WorkerCommand>) {
                     seen_idempotency.insert(key.clone());
                 }
                 let mut engines_locked = engines.lock().await;
-                let engine = engines_locked
-                    .entry(order.instrument_id)
-                    .or_insert_with(MatchingEngine::new);
-                let placed = engine.place_order(order);
-                let _ = response.send(Ok(placed));
+                let engine = match engine_for(&mut engines_locked, &instrument_configs, &mut batch_deadlines, order.instrument_id) {
+                    Ok(engine) => engine,
+                    Err(status) => {
+                        let _ = response.send(Err(status));
+                        continue;
+                    }
+                };
+                let (order_id, instrument_id) = (order.id, order.instrument_id);
+                let events = apply(engine, instrument_id, Command::Place(*order), &events_tx);
+                let _ = response.send(placed_with_fills(engine, order_id, events));
             }
             WorkerCommand::Cancel {
                 order_id,
@@ -289,16 +850,52 @@ async fn run_lane_worker(mut rx: mpsc::Receiver<WorkerCommand>) {
                     }
                 }
                 let mut engines_locked = engines.lock().await;
-                let cancelled = engines_locked
-                    .get_mut(&instrument_id)
-                    .and_then(|engine| engine.cancel_order(order_id));
-                let result =
-                    cancelled.ok_or_else(|| Status::not_found("Order not found or cannot be cancelled"));
+                let result = match engines_locked.get_mut(&instrument_id) {
+                    Some(engine) => {
+                        let events = apply(engine, instrument_id, Command::Cancel { order_id }, &events_tx);
+                        refusal(&events).and_then(|()| order_after(engine, order_id))
+                    }
+                    None => Err(status_from_engine_error(EngineError::UnknownOrder(order_id))),
+                };
                 if let (Ok(order), Some(key)) = (&result, idempotency_key) {
                     cancel_idempotency_results.insert(key, order.clone());
                 }
                 let _ = response.send(result);
             }
+            WorkerCommand::Amend {
+                order_id,
+                instrument_id,
+                price,
+                quantity,
+                sequencer,
+                idempotency_key,
+                response,
+            } => {
+                if let Some(key) = &idempotency_key {
+                    if let Some(existing) = amend_idempotency_results.get(key).cloned() {
+                        let _ = response.send(Ok(existing));
+                        continue;
+                    }
+                }
+                let mut engines_locked = engines.lock().await;
+                let result = match engines_locked.get_mut(&instrument_id) {
+                    Some(engine) => {
+                        let sequence = if engine.amend_requeues(order_id, price, quantity) {
+                            sequencer.next_sequence.fetch_add(1, Ordering::SeqCst)
+                        } else {
+                            0 // keeps its queue position, the sequence goes unused
+                        };
+                        let command = Command::Amend { order_id, price, quantity, sequence };
+                        let events = apply(engine, instrument_id, command, &events_tx);
+                        refusal(&events).and_then(|()| order_after(engine, order_id))
+                    }
+                    None => Err(status_from_engine_error(EngineError::UnknownOrder(order_id))),
+                };
+                if let (Ok(order), Some(key)) = (&result, idempotency_key) {
+                    amend_idempotency_results.insert(key, order.clone());
+                }
+                let _ = response.send(result);
+            }
             WorkerCommand::Snapshot {
                 depth,
                 instrument_id,
@@ -337,8 +934,8 @@ async fn run_lane_worker(mut rx: mpsc::Receiver<WorkerCommand>) {
                 let engines_locked = engines.lock().await;
                 let found = engines_locked
                     .get(&instrument_id)
-                    .and_then(|engine| engine.get_order_status(order_id).cloned());
-                let _ = response.send(found.ok_or_else(|| Status::not_found("Order not found")));
+                    .and_then(|engine| engine.get_order_status(order_id));
+                let _ = response.send(found.ok_or_else(|| status_from_engine_error(EngineError::UnknownOrder(order_id))));
             }
             WorkerCommand::Trades {
                 limit,
@@ -351,36 +948,113 @@ async fn run_lane_worker(mut rx: mpsc::Receiver<WorkerCommand>) {
                     let mut history = engine
                         .get_trade_history(Some(limit))
                         .into_iter()
-                        .map(|trade| ProtoTrade {
-                            maker_order_id: trade.maker_order_id,
-                            taker_order_id: trade.taker_order_id,
-                            price: Some(decimal_to_proto(trade.price)),
-                            quantity: Some(decimal_to_proto(trade.quantity)),
-                            side: match trade.side {
-                                Side::Bid => ProtoSide::Bid as i32,
-                                Side::Ask => ProtoSide::Ask as i32,
-                            },
-                            timestamp: trade.timestamp.map(|ts| Timestamp {
-                                seconds: ts.timestamp(),
-                                nanos: ts.timestamp_subsec_nanos() as i32,
-                            }),
-                            maker_sequence_number: trade.maker_sequence,
-                            taker_sequence_number: trade.taker_sequence,
-                            ingress_timestamp_ns: trade.ingress_timestamp_ns,
-                        })
+                        .map(|trade| trade_to_proto(&trade))
                         .collect::<Vec<_>>();
                     trades.append(&mut history);
                 }
                 let _ = response.send(Ok(trades));
             }
+            WorkerCommand::Trade {
+                trade_id,
+                instrument_id,
+                response,
+            } => {
+                let engines_locked = engines.lock().await;
+                let found = engines_locked
+                    .get(&instrument_id)
+                    .and_then(|engine| engine.get_trade(trade_id));
+                let _ = response.send(
+                    found
+                        .map(|trade| trade_to_proto(&trade))
+                        .ok_or_else(|| status_from_engine_error(EngineError::UnknownTrade(trade_id))),
+                );
+            }
+            WorkerCommand::SetPhase {
+                instrument_id,
+                phase,
+                response,
+            } => {
+                let mut engines_locked = engines.lock().await;
+                let engine = match engine_for(&mut engines_locked, &instrument_configs, &mut batch_deadlines, instrument_id) {
+                    Ok(engine) => engine,
+                    Err(status) => {
+                        let _ = response.send(Err(status));
+                        continue;
+                    }
+                };
+                let command = match (engine.trading_phase(), phase) {
+                    (TradingPhase::Continuous | TradingPhase::Batch, TradingPhase::Auction) => Some(Command::StartAuction),
+                    (TradingPhase::Auction, TradingPhase::Continuous) => Some(Command::Uncross),
+                    (TradingPhase::Continuous | TradingPhase::Batch | TradingPhase::Auction, TradingPhase::Halted) => {
+                        Some(Command::Halt)
+                    }
+                    (TradingPhase::Halted, TradingPhase::Continuous) => Some(Command::Resume),
+                    (TradingPhase::Halted, TradingPhase::Auction) => Some(Command::StartAuction),
+                    _ => None,
+                };
+                let mut uncross = command.and_then(|command| uncrossed(&apply(engine, instrument_id, command, &events_tx)));
+                // a halt that interrupted an auction resumes into it, so uncross on the way out
+                if phase == TradingPhase::Continuous && engine.trading_phase() == TradingPhase::Auction {
+                    uncross = uncrossed(&apply(engine, instrument_id, Command::Uncross, &events_tx));
+                }
+                let _ = response.send(Ok(auction_state_to_response(instrument_id, Some(engine), uncross)));
+            }
+            WorkerCommand::PlaceGroup {
+                group_id,
+                kind,
+                orders,
+                response,
+            } => {
+                let Some(instrument_id) = orders.first().map(|order| order.instrument_id) else {
+                    let _ = response.send(Err(Status::invalid_argument("Order group has no orders")));
+                    continue;
+                };
+                let mut engines_locked = engines.lock().await;
+                let engine = match engine_for(&mut engines_locked, &instrument_configs, &mut batch_deadlines, instrument_id) {
+                    Ok(engine) => engine,
+                    Err(status) => {
+                        let _ = response.send(Err(status));
+                        continue;
+                    }
+                };
+                let order_ids: Vec<u64> = orders.iter().map(|order| order.id).collect();
+                let command = match kind {
+                    GroupKind::Oco => Command::PlaceOco { group_id, legs: orders },
+                    GroupKind::Bracket => {
+                        let mut orders = orders.into_iter();
+                        match (orders.next(), orders.next(), orders.next()) {
+                            (Some(parent), Some(take_profit), Some(stop_loss)) => {
+                                Command::PlaceBracket { group_id, parent, take_profit, stop_loss }
+                            }
+                            _ => {
+                                let _ = response.send(Err(Status::invalid_argument(
+                                    "Bracket needs a parent, take-profit and stop-loss",
+                                )));
+                                continue;
+                            }
+                        }
+                    }
+                };
+                let events = apply(engine, instrument_id, command, &events_tx);
+                let placed = refusal(&events).and_then(|()| order_ids.iter().map(|id| order_after(engine, *id)).collect());
+                let _ = response.send(placed);
+            }
+            WorkerCommand::AuctionState {
+                instrument_id,
+                response,
+            } => {
+                let engines_locked = engines.lock().await;
+                let engine = engines_locked.get(&instrument_id);
+                let _ = response.send(Ok(auction_state_to_response(instrument_id, engine, None)));
+            }
         }
     }
 }
 
 #[tonic::async_trait]
 impl GrpcService for OrderBookService {
-    type StreamOrderBookStream = Pin<Box<dyn Stream<Item = Result<proto::OrderBookResponse, Status>> + Send>>;
-    type StreamTradeHistoryStream = Pin<Box<dyn Stream<Item = Result<ProtoTrade, Status>> + Send>>;
+    type stream_order_bookStream = Pin<Box<dyn Stream<Item = Result<proto::OrderBookResponse, Status>> + Send>>;
+    type stream_trade_historyStream = Pin<Box<dyn Stream<Item = Result<ProtoTrade, Status>> + Send>>;
 
     async fn place_order(
         &self,
@@ -391,14 +1065,15 @@ impl GrpcService for OrderBookService {
         let lane_sender = self.lane_sender_for_instrument(order.instrument_id).await;
         let (tx, rx) = oneshot::channel();
         lane_sender
-            .send(WorkerCommand::Place { order, response: tx })
+            .send(WorkerCommand::Place { order: Box::new(order), response: tx })
             .await
             .map_err(|_| Status::internal("Lane worker unavailable"))?;
-        let result = rx
+        let (result, fills) = rx
             .await
             .map_err(|_| Status::internal("Lane worker response dropped"))??;
 
-        Ok(Response::new(order_to_response(result)))
+        let price_scale = self.instrument_configs.get(&result.instrument_id).map_or(DEFAULT_SCALE, |config| config.price_scale);
+        Ok(Response::new(placed_to_response(result, fills, price_scale)))
     }
 
     // placeholder for now
@@ -454,6 +1129,96 @@ impl GrpcService for OrderBookService {
 	Ok(Response::new(order_to_response(cancelled_order)))
     }
 
+    async fn place_order_group(
+        &self,
+        request: Request<OrderGroupRequest>,
+    ) -> Result<Response<OrderGroupResponse>, Status> {
+        let req = request.into_inner();
+        let kind = match proto::OrderGroupType::try_from(req.group_type) {
+            Ok(proto::OrderGroupType::Oco) => GroupKind::Oco,
+            Ok(proto::OrderGroupType::Bracket) => GroupKind::Bracket,
+            _ => return Err(Status::invalid_argument("Invalid group_type")),
+        };
+        let Some(instrument_id) = req.orders.first().map(|order| order.instrument_id) else {
+            return Err(Status::invalid_argument("Order group has no orders"));
+        };
+        if req.orders.iter().any(|order| order.instrument_id != instrument_id) {
+            return Err(Status::invalid_argument("Order group spans instruments"));
+        }
+
+        let mut orders = Vec::with_capacity(req.orders.len());
+        for order in req.orders {
+            orders.push(self.build_engine_order(order).await?);
+        }
+        if kind == GroupKind::Bracket {
+            let [parent, take_profit, stop_loss] = orders.as_slice() else {
+                return Err(Status::invalid_argument("Bracket needs parent, take_profit and stop_loss"));
+            };
+            if take_profit.side == parent.side || stop_loss.side == parent.side {
+                return Err(Status::invalid_argument("Bracket children must be on the opposite side"));
+            }
+            if take_profit.order_type != OrderType::Limit {
+                return Err(Status::invalid_argument("take_profit must be a limit order"));
+            }
+            if !matches!(stop_loss.order_type, OrderType::Stop | OrderType::StopLimit) {
+                return Err(Status::invalid_argument("stop_loss must be a stop order"));
+            }
+        }
+
+        let lane = self.lane_sender_for_instrument(instrument_id).await;
+        let (tx, rx) = oneshot::channel();
+        lane.send(WorkerCommand::PlaceGroup {
+            group_id: req.group_id,
+            kind,
+            orders,
+            response: tx,
+        })
+        .await
+        .map_err(|_| Status::internal("Lane worker unavailable"))?;
+        let placed = rx
+            .await
+            .map_err(|_| Status::internal("Lane worker response dropped"))??;
+        Ok(Response::new(OrderGroupResponse {
+            orders: placed.into_iter().map(order_to_response).collect(),
+        }))
+    }
+
+    async fn amend_order(
+        &self,
+        request: Request<AmendOrderRequest>,
+    ) -> Result<Response<OrderResponse>, Status> {
+        let req = request.into_inner();
+        let price = match req.price.as_ref() {
+            Some(value) => Some(decimal_from_proto(Some(value), "price")?),
+            None => None,
+        };
+        let quantity = match req.quantity.as_ref() {
+            Some(value) => Some(decimal_from_proto(Some(value), "quantity")?),
+            None => None,
+        };
+        if price.is_none() && quantity.is_none() {
+            return Err(Status::invalid_argument("Amend needs a price or quantity"));
+        }
+        let sequencer = self.state_for_instrument(req.instrument_id).await;
+        let lane = self.lane_sender_for_instrument(req.instrument_id).await;
+        let (tx, rx) = oneshot::channel();
+        lane.send(WorkerCommand::Amend {
+            order_id: req.order_id,
+            instrument_id: req.instrument_id,
+            price,
+            quantity,
+            sequencer,
+            idempotency_key: req.idempotency_key,
+            response: tx,
+        })
+        .await
+        .map_err(|_| Status::internal("Lane worker unavailable"))?;
+        let amended_order = rx
+            .await
+            .map_err(|_| Status::internal("Lane worker response dropped"))??;
+        Ok(Response::new(order_to_response(amended_order)))
+    }
+
     async fn cancel_orders(
         &self,
         request: Request<CancelOrderBatchRequest>,
@@ -497,7 +1262,7 @@ impl GrpcService for OrderBookService {
     async fn stream_order_book(
         &self,
         request: Request<StreamOrderBookRequest>,
-    ) -> Result<Response<Self::StreamOrderBookStream>, Status> {
+    ) -> Result<Response<Self::stream_order_bookStream>, Status> {
         let req = request.into_inner();
         let interval = Duration::from_millis(req.interval_ms.max(100) as u64);
         let service = self.clone();
@@ -519,6 +1284,54 @@ impl GrpcService for OrderBookService {
         Ok(Response::new(Box::pin(stream)))
     }
 
+    async fn set_trading_phase(
+        &self,
+        request: Request<SetTradingPhaseRequest>,
+    ) -> Result<Response<AuctionStateResponse>, Status> {
+        let req = request.into_inner();
+        let phase = match proto::TradingPhase::try_from(req.phase) {
+            Ok(proto::TradingPhase::Continuous) => TradingPhase::Continuous,
+            Ok(proto::TradingPhase::Auction) => TradingPhase::Auction,
+            Ok(proto::TradingPhase::Halted) => TradingPhase::Halted,
+            Ok(proto::TradingPhase::Batch) => {
+                return Err(Status::invalid_argument("BATCH comes from the instrument's batch_interval"))
+            }
+            _ => return Err(Status::invalid_argument("Invalid phase")),
+        };
+        let lane = self.lane_sender_for_instrument(req.instrument_id).await;
+        let (tx, rx) = oneshot::channel();
+        lane.send(WorkerCommand::SetPhase {
+            instrument_id: req.instrument_id,
+            phase,
+            response: tx,
+        })
+        .await
+        .map_err(|_| Status::internal("Lane worker unavailable"))?;
+        let state = rx
+            .await
+            .map_err(|_| Status::internal("Lane worker response dropped"))??;
+        Ok(Response::new(state))
+    }
+
+    async fn get_auction_state(
+        &self,
+        request: Request<GetAuctionStateRequest>,
+    ) -> Result<Response<AuctionStateResponse>, Status> {
+        let req = request.into_inner();
+        let lane = self.lane_sender_for_instrument(req.instrument_id).await;
+        let (tx, rx) = oneshot::channel();
+        lane.send(WorkerCommand::AuctionState {
+            instrument_id: req.instrument_id,
+            response: tx,
+        })
+        .await
+        .map_err(|_| Status::internal("Lane worker unavailable"))?;
+        let state = rx
+            .await
+            .map_err(|_| Status::internal("Lane worker response dropped"))??;
+        Ok(Response::new(state))
+    }
+
     async fn get_order_status(
         &self,
         request: Request<GetOrderStatusRequest>,
@@ -558,10 +1371,27 @@ impl GrpcService for OrderBookService {
 	Ok(Response::new(TradeHistoryResponse { trades }))
     }
 
+    async fn get_trade(&self, request: Request<GetTradeRequest>) -> Result<Response<ProtoTrade>, Status> {
+        let req = request.into_inner();
+        let lane = self.lane_sender_for_instrument(req.instrument_id).await;
+        let (tx, rx) = oneshot::channel();
+        lane.send(WorkerCommand::Trade {
+            trade_id: req.trade_id,
+            instrument_id: req.instrument_id,
+            response: tx,
+        })
+        .await
+        .map_err(|_| Status::internal("Lane worker unavailable"))?;
+        let trade = rx
+            .await
+            .map_err(|_| Status::internal("Lane worker response dropped"))??;
+        Ok(Response::new(trade))
+    }
+
     async fn stream_trade_history(
         &self,
         request: Request<StreamTradeHistoryRequest>,
-    ) -> Result<Response<Self::StreamTradeHistoryStream>, Status> {
+    ) -> Result<Response<Self::stream_trade_historyStream>, Status> {
         let req = request.into_inner();
         let interval = Duration::from_millis(req.interval_ms.max(100) as u64);
         let service = self.clone();
@@ -0,0 +1,73 @@
+use std::collections::{BTreeMap, BTreeSet};
+use rust_decimal::Decimal;
+use super::types::{Ticks, Volume};
+
+/// the single price a call auction uncrosses at and what trades there
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Uncross<P = Decimal, Q = P> {
+    pub price: P,
+    pub volume: Q,
+    pub imbalance: Q, // |demand - supply| left at `price`
+}
+
+/// equilibrium price for a crossed book: maximise executable volume, then minimise
+/// imbalance, then take the price closest to `reference` (the lowest tied price when
+/// there is no reference). None if nothing crosses. `bids` and `asks` map each price
+/// to the open quantity there.
+pub fn equilibrium(
+    bids: &BTreeMap<Ticks, Volume>,
+    asks: &BTreeMap<Ticks, Volume>,
+    reference: Option<Ticks>,
+) -> Option<Uncross<Ticks, Volume>> {
+    // every limit price on either side is a candidate
+    let candidates: Vec<Ticks> = bids.keys().chain(asks.keys()).copied().collect::<BTreeSet<_>>().into_iter().collect();
+
+    // demand at p = bids priced >= p, accumulated from the top down
+    let mut demand = vec![0; candidates.len()];
+    let mut bid_levels = bids.iter().rev().peekable();
+    let mut cumulative = 0;
+    for (idx, price) in candidates.iter().enumerate().rev() {
+        while let Some((_, quantity)) = bid_levels.next_if(|(bid, _)| *bid >= price) {
+            cumulative += quantity;
+        }
+        demand[idx] = cumulative;
+    }
+
+    // supply at p = asks priced <= p, accumulated from the bottom up
+    let mut best: Option<Uncross<Ticks, Volume>> = None;
+    let mut ask_levels = asks.iter().peekable();
+    let mut supply = 0;
+    for (idx, price) in candidates.iter().enumerate() {
+        while let Some((_, quantity)) = ask_levels.next_if(|(ask, _)| *ask <= price) {
+            supply += quantity;
+        }
+        let volume = demand[idx].min(supply);
+        if volume == 0 {
+            continue;
+        }
+        let candidate = Uncross {
+            price: *price,
+            volume,
+            imbalance: (demand[idx] - supply).abs(),
+        };
+        best = match best {
+            Some(current) if !better(&candidate, &current, reference) => Some(current),
+            _ => Some(candidate),
+        };
+    }
+    best
+}
+
+fn better(candidate: &Uncross<Ticks, Volume>, current: &Uncross<Ticks, Volume>, reference: Option<Ticks>) -> bool {
+    if candidate.volume != current.volume {
+        return candidate.volume > current.volume;
+    }
+    if candidate.imbalance != current.imbalance {
+        return candidate.imbalance < current.imbalance;
+    }
+    // candidates arrive in ascending price order, so ties without a reference keep the lower
+    match reference {
+        Some(reference) => (candidate.price - reference).abs() < (current.price - reference).abs(),
+        None => false,
+    }
+}
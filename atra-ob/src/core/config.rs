@@ -0,0 +1,68 @@
+use std::sync::Arc;
+use std::time::Duration;
+use rust_decimal::Decimal;
+use super::matching_policy::{Fifo, MatchingPolicy};
+
+/// decimal places prices and quantities are held to unless configured otherwise
+pub const DEFAULT_SCALE: u32 = 8;
+
+/// per-instrument settings the engine is created with
+#[derive(Debug, Clone)]
+pub struct InstrumentConfig {
+    /// minimum price increment, used when repricing post-only orders
+    pub tick_size: Decimal,
+    /// decimal places prices are held to inside the book; finer prices are rejected
+    pub price_scale: u32,
+    /// decimal places quantities are held to inside the book; finer quantities are rejected
+    pub quantity_scale: u32,
+    /// how fills are shared across the orders at a price level
+    pub matching_policy: Arc<dyn MatchingPolicy>,
+    /// run as a frequent batch auction, clearing at this interval, instead of matching continuously
+    pub batch_interval: Option<Duration>,
+    /// furthest a market-type order may sweep past the touch on arrival, as a percentage
+    /// of the touch (1.5 = 1.5%)
+    pub max_slippage: Option<Decimal>,
+    /// largest single order quantity accepted, anything over is a risk reject
+    pub max_order_quantity: Option<Decimal>,
+    /// whether an order id can be used again once its order is done with
+    pub id_reuse: IdReuse,
+    /// how the book stores its price levels
+    pub price_ladder: PriceLadder,
+}
+
+/// ids of working orders are never accepted twice; this covers ids whose order has
+/// filled, been cancelled, rejected or expired
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum IdReuse {
+    #[default]
+    Never,
+    AfterTerminal,
+}
+
+/// price level storage. both behave the same; they differ only in speed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PriceLadder {
+    /// a sorted map, good for any spread of prices
+    #[default]
+    Tree,
+    /// an array of `ticks` levels, one per tick size, centred on `reference_price`.
+    /// faster for liquid instruments trading in a narrow band; prices off the array
+    /// still work but go through a map.
+    Dense { reference_price: Decimal, ticks: usize },
+}
+
+impl Default for InstrumentConfig {
+    fn default() -> Self {
+        Self {
+            tick_size: Decimal::new(1, 8), // same resolution as the inq fixed point (price*10^8)
+            price_scale: DEFAULT_SCALE,
+            quantity_scale: DEFAULT_SCALE,
+            matching_policy: Arc::new(Fifo),
+            batch_interval: None,
+            max_slippage: None,
+            max_order_quantity: None,
+            id_reuse: IdReuse::Never,
+            price_ladder: PriceLadder::Tree,
+        }
+    }
+}
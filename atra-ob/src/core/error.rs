@@ -0,0 +1,43 @@
+use std::fmt;
+use rust_decimal::Decimal;
+use super::types::OrderStatus;
+
+/// why the engine refused a request outright. an order that was accepted and then
+/// rejected or cancelled by the rules (post-only, FOK, ...) is not an error; it comes
+/// back as an order with a terminal status and a reason.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EngineError {
+    DuplicateOrderId(u64),
+    UnknownOrder(u64),
+    OrderClosed { order_id: u64, status: OrderStatus }, // already filled, cancelled, ...
+    NotAmendable(u64),                                  // not resting on the lit book, or a pegged order's price
+    InvalidPrice(Decimal),
+    InvalidQuantity(Decimal),
+    InstrumentHalted(u32),
+    RiskRejected(String),
+    DuplicateGroupId(u64),
+    UnknownTrade(u64),
+    UnsupportedOrder(String), // a combination of order flags the engine doesn't handle
+    InvalidConfig(String),    // instrument settings the engine can't run with
+}
+
+impl fmt::Display for EngineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::DuplicateOrderId(id) => write!(f, "order id {id} is already in use"),
+            Self::UnknownOrder(id) => write!(f, "order {id} not found"),
+            Self::OrderClosed { order_id, status } => write!(f, "order {order_id} is already {status:?}"),
+            Self::NotAmendable(id) => write!(f, "order {id} can't be amended that way"),
+            Self::InvalidPrice(price) => write!(f, "invalid price {price}"),
+            Self::InvalidQuantity(quantity) => write!(f, "invalid quantity {quantity}"),
+            Self::InstrumentHalted(instrument_id) => write!(f, "instrument {instrument_id} is halted"),
+            Self::RiskRejected(reason) => write!(f, "risk check failed: {reason}"),
+            Self::DuplicateGroupId(id) => write!(f, "order group {id} already exists"),
+            Self::UnknownTrade(id) => write!(f, "trade {id} not found"),
+            Self::UnsupportedOrder(reason) => write!(f, "unsupported order: {reason}"),
+            Self::InvalidConfig(reason) => write!(f, "invalid instrument config: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for EngineError {}
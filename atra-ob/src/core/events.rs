@@ -0,0 +1,101 @@
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use super::auction::Uncross;
+use super::error::EngineError;
+use super::trade_history::Trade;
+use super::types::{Order, Side, TradingPhase};
+
+/// a request to the engine, as fed to `MatchingEngine::apply`
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[allow(clippy::large_enum_variant)] // moved once per command, boxing the order would only add an allocation
+pub enum Command {
+    Place(Order),
+    Cancel { order_id: u64 },
+    Amend { order_id: u64, price: Option<Decimal>, quantity: Option<Decimal>, sequence: u64 },
+    /// expire every GTD/DAY order due at or before `now`
+    ExpireOrders { now: DateTime<Utc> },
+    PlaceOco { group_id: u64, legs: Vec<Order> },
+    PlaceBracket { group_id: u64, parent: Order, take_profit: Order, stop_loss: Order },
+    StartAuction,
+    /// end the call auction at its equilibrium price
+    Uncross,
+    /// one frequent-batch-auction clear
+    ClearBatch,
+    Halt,
+    Resume,
+}
+
+/// something a command did, reported in the order it happened. applying the same
+/// commands to a fresh engine gives the same events.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EngineEvent {
+    /// the command was refused outright and changed nothing. a group is reported
+    /// under its first order.
+    CommandRejected { order_id: u64, error: EngineError },
+    /// an order passed validation and is about to be worked
+    OrderAccepted(Order),
+    /// an accepted order the rules turned away (post-only, FOK, ...)
+    OrderRejected(Order),
+    /// an order went onto the book (or the midpoint book) with what it has left
+    OrderRested(Order),
+    /// an order is waiting to be worked: a stop on its trigger price, or a bracket
+    /// child on its parent filling
+    OrderParked(Order),
+    /// a resting order was changed without losing its place
+    OrderAmended(Order),
+    Fill(Fill),
+    OrderCancelled(Order),
+    OrderExpired(Order),
+    PhaseChanged(TradingPhase),
+    /// an auction or batch is about to execute at this price; its fills follow
+    Uncrossed(Uncross),
+    /// the displayed quantity at a lit price level, after the command
+    BookLevelChanged { side: Side, price: Decimal, quantity: Decimal },
+}
+
+/// one side of a trade
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fill {
+    pub trade_id: u64,     // counts up from 1 per instrument
+    pub execution_id: u64, // this side's execution, see `Trade`
+    pub order_id: u64,
+    pub counterparty_order_id: u64,
+    pub side: Side,
+    pub liquidity: Liquidity,
+    pub price: Decimal,
+    pub quantity: Decimal,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Liquidity {
+    Maker,
+    Taker,
+}
+
+impl Fill {
+    /// the resting order's id, whichever side this is
+    pub fn maker_order_id(&self) -> u64 {
+        match self.liquidity {
+            Liquidity::Maker => self.order_id,
+            Liquidity::Taker => self.counterparty_order_id,
+        }
+    }
+
+    /// the maker's and the taker's fill, in that order
+    pub(crate) fn sides(trade: &Trade) -> [Fill; 2] {
+        let fill = |execution_id, order_id, counterparty_order_id, side, liquidity| Fill {
+            trade_id: trade.trade_id,
+            execution_id,
+            order_id,
+            counterparty_order_id,
+            side,
+            liquidity,
+            price: trade.price,
+            quantity: trade.quantity,
+        };
+        [
+            fill(trade.maker_execution_id, trade.maker_order_id, trade.taker_order_id, trade.side.opposite(), Liquidity::Maker),
+            fill(trade.taker_execution_id, trade.taker_order_id, trade.maker_order_id, trade.side, Liquidity::Taker),
+        ]
+    }
+}
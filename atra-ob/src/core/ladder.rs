@@ -0,0 +1,257 @@
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+use std::ops::Bound::{Excluded, Unbounded};
+use std::ops::ControlFlow;
+use super::price_level::Level;
+use super::scale;
+use super::types::Ticks;
+
+/// the price levels on one side of the book, keyed by price. a level is only held
+/// while it has orders in it: `open` comes before a push and `close` after the last
+/// order leaves.
+pub(crate) trait Ladder: Debug + Send + Sync {
+    fn get(&self, price: Ticks) -> Option<&Level>;
+
+    fn get_mut(&mut self, price: Ticks) -> Option<&mut Level>;
+
+    /// the level at `price`, made if there isn't one
+    fn open(&mut self, price: Ticks) -> &mut Level;
+
+    fn close(&mut self, price: Ticks);
+
+    fn lowest(&self) -> Option<Ticks>;
+
+    fn highest(&self) -> Option<Ticks>;
+
+    /// the first level priced strictly above `price`
+    fn above(&self, price: Ticks) -> Option<Ticks>;
+
+    /// the first level priced strictly below `price`
+    fn below(&self, price: Ticks) -> Option<Ticks>;
+
+    /// visit levels in price order, low to high if `ascending`, until `visit` breaks
+    fn walk(&self, ascending: bool, visit: &mut dyn FnMut(Ticks, &Level) -> ControlFlow<()>);
+}
+
+impl Ladder for BTreeMap<Ticks, Level> {
+    fn get(&self, price: Ticks) -> Option<&Level> {
+        BTreeMap::get(self, &price)
+    }
+
+    fn get_mut(&mut self, price: Ticks) -> Option<&mut Level> {
+        BTreeMap::get_mut(self, &price)
+    }
+
+    fn open(&mut self, price: Ticks) -> &mut Level {
+        self.entry(price).or_default()
+    }
+
+    fn close(&mut self, price: Ticks) {
+        self.remove(&price);
+    }
+
+    fn lowest(&self) -> Option<Ticks> {
+        self.keys().next().copied()
+    }
+
+    fn highest(&self) -> Option<Ticks> {
+        self.keys().next_back().copied()
+    }
+
+    fn above(&self, price: Ticks) -> Option<Ticks> {
+        self.range((Excluded(price), Unbounded)).next().map(|(price, _)| *price)
+    }
+
+    fn below(&self, price: Ticks) -> Option<Ticks> {
+        self.range(..price).next_back().map(|(price, _)| *price)
+    }
+
+    fn walk(&self, ascending: bool, visit: &mut dyn FnMut(Ticks, &Level) -> ControlFlow<()>) {
+        let _ = if ascending {
+            self.iter().try_for_each(|(price, level)| visit(*price, level))
+        } else {
+            self.iter().rev().try_for_each(|(price, level)| visit(*price, level))
+        };
+    }
+}
+
+/// levels in a contiguous array, one slot per tick from `base`, with cursors on the
+/// lowest and highest occupied slots. prices off the window (or off the tick grid)
+/// still work, they just fall back to a map. meant for liquid instruments trading in a
+/// narrow range, where the book is dense around the touch.
+#[derive(Debug)]
+pub(crate) struct DenseLadder {
+    base: Ticks,         // price of slots[0]
+    tick_size: Ticks,    // price step between slots
+    slots: Vec<Level>,
+    low: Option<usize>,  // lowest occupied slot
+    high: Option<usize>, // highest occupied slot
+    outside: BTreeMap<Ticks, Level>,
+}
+
+impl DenseLadder {
+    /// a window of `ticks` slots centred on `reference`
+    pub(crate) fn new(reference: Ticks, tick_size: Ticks, ticks: usize) -> Self {
+        let tick_size = tick_size.max(1);
+        let half = Ticks::try_from(ticks / 2).unwrap_or(Ticks::MAX);
+        let base = scale::round_down(reference.saturating_sub(half.saturating_mul(tick_size)), tick_size);
+        Self {
+            base,
+            tick_size,
+            slots: (0..ticks).map(|_| Level::default()).collect(),
+            low: None,
+            high: None,
+            outside: BTreeMap::new(),
+        }
+    }
+
+    /// the slot `price` lives in, if it's on the window
+    fn slot(&self, price: Ticks) -> Option<usize> {
+        let offset = price.checked_sub(self.base)?;
+        if offset < 0 || offset % self.tick_size != 0 {
+            return None;
+        }
+        let idx = usize::try_from(offset / self.tick_size).ok()?;
+        (idx < self.slots.len()).then_some(idx)
+    }
+
+    fn price_of(&self, idx: usize) -> Ticks {
+        self.base + idx as Ticks * self.tick_size
+    }
+
+    fn occupied(&self, idx: usize) -> bool {
+        !self.slots[idx].is_empty()
+    }
+
+    /// occupied slots, low to high
+    fn window(&self) -> impl DoubleEndedIterator<Item = (Ticks, &Level)> + '_ {
+        let range = match (self.low, self.high) {
+            (Some(low), Some(high)) => low..high + 1,
+            _ => 0..0,
+        };
+        range
+            .filter(|&idx| self.occupied(idx))
+            .map(|idx| (self.price_of(idx), &self.slots[idx]))
+    }
+
+    /// the first occupied slot at or after `from`, going up
+    fn first_up(&self, from: usize) -> Option<usize> {
+        let (low, high) = (self.low?, self.high?);
+        (from.max(low)..=high).find(|&idx| self.occupied(idx))
+    }
+
+    /// the first occupied slot at or before `from`, going down
+    fn first_down(&self, from: usize) -> Option<usize> {
+        let (low, high) = (self.low?, self.high?);
+        (low..=from.min(high)).rev().find(|&idx| self.occupied(idx))
+    }
+}
+
+impl Ladder for DenseLadder {
+    fn get(&self, price: Ticks) -> Option<&Level> {
+        match self.slot(price) {
+            Some(idx) => self.occupied(idx).then_some(&self.slots[idx]),
+            None => self.outside.get(&price),
+        }
+    }
+
+    fn get_mut(&mut self, price: Ticks) -> Option<&mut Level> {
+        match self.slot(price) {
+            Some(idx) => self.occupied(idx).then_some(&mut self.slots[idx]),
+            None => self.outside.get_mut(&price),
+        }
+    }
+
+    fn open(&mut self, price: Ticks) -> &mut Level {
+        let Some(idx) = self.slot(price) else {
+            return self.outside.entry(price).or_default();
+        };
+        self.low = Some(self.low.map_or(idx, |low| low.min(idx)));
+        self.high = Some(self.high.map_or(idx, |high| high.max(idx)));
+        &mut self.slots[idx]
+    }
+
+    fn close(&mut self, price: Ticks) {
+        let Some(idx) = self.slot(price) else {
+            self.outside.remove(&price);
+            return;
+        };
+        self.slots[idx] = Level::default();
+        if self.low == Some(idx) {
+            self.low = self.first_up(idx);
+        }
+        if self.high == Some(idx) {
+            self.high = idx.checked_sub(1).and_then(|idx| self.first_down(idx));
+        }
+        if self.low.is_none() || self.high.is_none() {
+            (self.low, self.high) = (None, None);
+        }
+    }
+
+    fn lowest(&self) -> Option<Ticks> {
+        let window = self.low.map(|idx| self.price_of(idx));
+        let outside = self.outside.keys().next().copied();
+        window.into_iter().chain(outside).min()
+    }
+
+    fn highest(&self) -> Option<Ticks> {
+        let window = self.high.map(|idx| self.price_of(idx));
+        let outside = self.outside.keys().next_back().copied();
+        window.into_iter().chain(outside).max()
+    }
+
+    fn above(&self, price: Ticks) -> Option<Ticks> {
+        let from = if price < self.base {
+            Some(0)
+        } else {
+            usize::try_from((price - self.base) / self.tick_size + 1).ok()
+        };
+        let window = from.and_then(|from| self.first_up(from)).map(|idx| self.price_of(idx));
+        window.into_iter().chain(self.outside.above(price)).min()
+    }
+
+    fn below(&self, price: Ticks) -> Option<Ticks> {
+        // the last slot priced under `price`
+        let to = if price <= self.base {
+            None
+        } else {
+            usize::try_from((price - self.base - 1) / self.tick_size).ok()
+        };
+        let window = to.and_then(|to| self.first_down(to)).map(|idx| self.price_of(idx));
+        window.into_iter().chain(self.outside.below(price)).max()
+    }
+
+    fn walk(&self, ascending: bool, visit: &mut dyn FnMut(Ticks, &Level) -> ControlFlow<()>) {
+        let outside = self.outside.iter().map(|(price, level)| (*price, level));
+        if ascending {
+            merge(self.window(), outside, |a, b| a < b, visit);
+        } else {
+            merge(self.window().rev(), outside.rev(), |a, b| a > b, visit);
+        }
+    }
+}
+
+/// visit two price-ordered runs of levels as one, taking from `a` while `first` says
+/// its price comes first
+fn merge<'a>(
+    a: impl Iterator<Item = (Ticks, &'a Level)>,
+    b: impl Iterator<Item = (Ticks, &'a Level)>,
+    first: impl Fn(Ticks, Ticks) -> bool,
+    visit: &mut dyn FnMut(Ticks, &Level) -> ControlFlow<()>,
+) {
+    let (mut a, mut b) = (a.peekable(), b.peekable());
+    loop {
+        let next = match (a.peek(), b.peek()) {
+            (Some(x), Some(y)) if first(x.0, y.0) => a.next(),
+            (Some(_), Some(_)) => b.next(),
+            (Some(_), None) => a.next(),
+            (None, _) => b.next(),
+        };
+        let Some((price, level)) = next else {
+            return;
+        };
+        if visit(price, level).is_break() {
+            return;
+        }
+    }
+}
@@ -0,0 +1,162 @@
+use std::fmt::Debug;
+use std::sync::Arc;
+use rust_decimal::Decimal;
+use super::config::DEFAULT_SCALE;
+use super::scale;
+use super::types::{Lots, Order, Volume};
+
+/// how an incoming quantity is shared out across the resting orders at one price level
+pub trait MatchingPolicy: Debug + Send + Sync {
+    /// split `quantity` across `resting` (the level in time priority, as the book holds
+    /// it, in lots). returns (index into `resting`, fill quantity) pairs in the order the
+    /// fills execute. fills must be positive, never exceed an order's visible quantity,
+    /// and sum to at most `quantity`.
+    fn allocate(&self, quantity: Lots, resting: &[&Order<i64>]) -> Vec<(usize, Lots)>;
+
+    /// whether the policy only ever fills from the front of the queue until `quantity`
+    /// runs out. if so the engine hands it just enough of the level to cover the
+    /// quantity instead of the whole queue.
+    fn fills_from_front(&self) -> bool {
+        false
+    }
+
+    /// the policy as it runs on a book holding quantities to `quantity_scale` decimal
+    /// places, for policies configured with sizes that have to be turned into lots
+    /// first. the engine calls this once, when it's created; None runs the policy as is.
+    fn in_lots(&self, _quantity_scale: u32) -> Option<Arc<dyn MatchingPolicy>> {
+        None
+    }
+}
+
+/// price-time priority: the front of the queue fills first
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Fifo;
+
+impl MatchingPolicy for Fifo {
+    fn allocate(&self, quantity: Lots, resting: &[&Order<i64>]) -> Vec<(usize, Lots)> {
+        let mut allocations = Vec::new();
+        let mut left = quantity;
+        for (idx, order) in resting.iter().enumerate() {
+            if left == 0 {
+                break;
+            }
+            let fill = left.min(order.visible_quantity());
+            if fill > 0 {
+                allocations.push((idx, fill));
+                left -= fill;
+            }
+        }
+        allocations
+    }
+
+    fn fills_from_front(&self) -> bool {
+        true
+    }
+}
+
+/// pro-rata by visible size. shares are rounded down to `lot_size`, shares under
+/// `min_allocation` are dropped, and whatever is left over goes out FIFO. both are
+/// quantities like an order's, turned into lots at the instrument's quantity scale.
+#[derive(Debug, Clone, Copy)]
+pub struct ProRata<A = Decimal> {
+    pub min_allocation: A,
+    pub lot_size: A,
+}
+
+impl ProRata {
+    /// the sizes in lots, rounded down to a whole lot
+    fn lots(&self, quantity_scale: u32) -> ProRata<Lots> {
+        let lots = |size| scale::floor_fixed(size, quantity_scale).unwrap_or(Lots::MAX);
+        ProRata { min_allocation: lots(self.min_allocation), lot_size: lots(self.lot_size) }
+    }
+}
+
+impl ProRata<Lots> {
+    fn split(&self, quantity: Lots, resting: &[&Order<i64>], skip: usize) -> Vec<(usize, Lots)> {
+        let total: Volume = resting.iter().skip(skip).map(|o| Volume::from(o.visible_quantity())).sum();
+        // no more than `quantity`, so it fits back in lots
+        let quantity = Volume::from(quantity).min(total) as Lots;
+        if quantity == 0 {
+            return Vec::new();
+        }
+
+        let mut shares: Vec<Lots> = resting
+            .iter()
+            .skip(skip)
+            .map(|order| {
+                // widened so the product can't overflow; the share itself is at most `quantity`
+                let mut share = (Volume::from(quantity) * Volume::from(order.visible_quantity()) / total) as Lots;
+                if self.lot_size > 0 {
+                    share = share / self.lot_size * self.lot_size;
+                }
+                if share < self.min_allocation { 0 } else { share }
+            })
+            .collect();
+
+        // leftover from rounding and the minimum goes to the queue in time order
+        let mut left = quantity - shares.iter().sum::<Lots>();
+        for (share, order) in shares.iter_mut().zip(resting.iter().skip(skip)) {
+            if left == 0 {
+                break;
+            }
+            let extra = left.min(order.visible_quantity() - *share);
+            *share += extra;
+            left -= extra;
+        }
+
+        shares
+            .into_iter()
+            .enumerate()
+            .filter(|(_, share)| *share > 0)
+            .map(|(idx, share)| (idx + skip, share))
+            .collect()
+    }
+}
+
+impl MatchingPolicy for ProRata<Lots> {
+    fn allocate(&self, quantity: Lots, resting: &[&Order<i64>]) -> Vec<(usize, Lots)> {
+        self.split(quantity, resting, 0)
+    }
+}
+
+/// run outside an engine, the sizes are taken at the default quantity scale
+impl MatchingPolicy for ProRata {
+    fn allocate(&self, quantity: Lots, resting: &[&Order<i64>]) -> Vec<(usize, Lots)> {
+        self.lots(DEFAULT_SCALE).allocate(quantity, resting)
+    }
+
+    fn in_lots(&self, quantity_scale: u32) -> Option<Arc<dyn MatchingPolicy>> {
+        Some(Arc::new(self.lots(quantity_scale)))
+    }
+}
+
+/// the order at the front of the queue fills first, the rest is shared pro-rata
+#[derive(Debug, Clone, Copy)]
+pub struct TopOrderProRata<A = Decimal> {
+    pub pro_rata: ProRata<A>,
+}
+
+impl MatchingPolicy for TopOrderProRata {
+    fn allocate(&self, quantity: Lots, resting: &[&Order<i64>]) -> Vec<(usize, Lots)> {
+        TopOrderProRata { pro_rata: self.pro_rata.lots(DEFAULT_SCALE) }.allocate(quantity, resting)
+    }
+
+    fn in_lots(&self, quantity_scale: u32) -> Option<Arc<dyn MatchingPolicy>> {
+        Some(Arc::new(TopOrderProRata { pro_rata: self.pro_rata.lots(quantity_scale) }))
+    }
+}
+
+impl MatchingPolicy for TopOrderProRata<Lots> {
+    fn allocate(&self, quantity: Lots, resting: &[&Order<i64>]) -> Vec<(usize, Lots)> {
+        let Some(top) = resting.first() else {
+            return Vec::new();
+        };
+        let top_fill = quantity.min(top.visible_quantity());
+        let mut allocations = Vec::new();
+        if top_fill > 0 {
+            allocations.push((0, top_fill));
+        }
+        allocations.extend(self.pro_rata.split(quantity - top_fill, resting, 1));
+        allocations
+    }
+}
@@ -1,127 +1,1469 @@
+use chrono::{DateTime, Utc};
+use rust_decimal::prelude::ToPrimitive;
 use rust_decimal::Decimal;
+use super::auction::{self, Uncross};
+use super::config::{IdReuse, InstrumentConfig};
+use super::error::EngineError;
+use super::events::{Command, EngineEvent, Fill};
+use super::midpoint_book::MidpointBook;
+use super::order_group::OrderGroup;
+use super::matching_policy::MatchingPolicy;
 use super::orderbook::OrderBook;
-use super::types::{Order, Side, OrderType, OrderStatus};
+use super::price_level::{Level, Node};
+use super::scale::{self, BookOrder, Scale};
+use super::slab::{Slab, SlabKey};
+use super::types::{Lots, Order, Side, OrderType, OrderStatus, GroupKind, GroupMembership, GroupRole, Peg, PegType, PostOnly, PriceLevels, StatusReason, StpEvent, StpMode, Ticks, TimeInForce, TradingPhase, Volume};
 use super::trade_history::{TxnHistory, Trade};
-use std::collections::VecDeque;
+use super::trigger_book::TriggerBook;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::ops::ControlFlow;
+use std::sync::Arc;
 
 pub struct MatchingEngine {
+    config: InstrumentConfig,
+    phase: TradingPhase,
+    halted_from: TradingPhase,                // the phase the current (or last) halt interrupted
     order_book: OrderBook,
     trade_history: TxnHistory,
+    triggers: TriggerBook,
+    expiries: BTreeSet<(DateTime<Utc>, u64)>, // GTD/DAY resting orders by expiry
+    pegs: BTreeSet<u64>,                      // pegged orders that may still be resting
+    groups: HashMap<u64, OrderGroup>,         // live OCO/bracket groups by group id
+    group_fills: Vec<u64>,                    // group members that traded since the last settle
+    group_exits: Vec<(u64, GroupMembership)>, // group members that finished otherwise since then
+    midpoint_book: MidpointBook,
+    policy: Arc<dyn MatchingPolicy>,          // the config's, with its sizes in lots
+    scale: Scale,                             // fixed point the book works in
+    tick_size: Ticks,                         // the config's, in ticks
+    max_order_quantity: Option<Lots>,         // the config's, in lots
+    last_price: Option<Ticks>,                // last trade price, what stops and market-to-limit go by
+    events: Option<Vec<EngineEvent>>,         // what the command being applied has done so far
+    last_trade_id: u64,                       // id of the latest trade, 0 before the first
+}
+
+impl Default for MatchingEngine {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl MatchingEngine {
     pub fn new() -> Self {
-        Self {
-            order_book: OrderBook::new(),
-	    trade_history: TxnHistory::new()
+        Self::with_config(InstrumentConfig::default()).expect("the default config is valid")
+    }
+
+    /// an engine for one instrument; refused if the config's scales can't hold its
+    /// prices and quantities
+    pub fn with_config(config: InstrumentConfig) -> Result<Self, EngineError> {
+        let phase = Self::resting_phase(&config);
+        let scale = Scale::of(&config)?;
+        // a tick size finer than the price scale falls back to the smallest step there is
+        let tick_size = scale.config_ticks("tick_size", config.tick_size)?.max(1);
+        let max_order_quantity = config
+            .max_order_quantity
+            .map(|max| scale.config_lots("max_order_quantity", max))
+            .transpose()?;
+        let policy = config.matching_policy.in_lots(config.quantity_scale).unwrap_or_else(|| config.matching_policy.clone());
+        let order_book = OrderBook::with_config(&config)?;
+        Ok(Self {
+            config,
+            phase,
+            halted_from: phase,
+            order_book,
+	    trade_history: TxnHistory::new(),
+	    triggers: TriggerBook::new(),
+	    expiries: BTreeSet::new(),
+	    pegs: BTreeSet::new(),
+	    groups: HashMap::new(),
+	    group_fills: Vec::new(),
+	    group_exits: Vec::new(),
+	    midpoint_book: MidpointBook::new(),
+	    policy,
+	    scale,
+	    tick_size,
+	    max_order_quantity,
+	    last_price: None,
+	    events: None,
+	    last_trade_id: 0,
+        })
+    }
+
+    /// run one command, returning everything it did: accepted/rested/cancelled/expired
+    /// orders and fills as they happened, then the lit levels whose displayed
+    /// quantity changed
+    pub fn apply(&mut self, command: Command) -> Vec<EngineEvent> {
+        self.events = Some(Vec::new());
+        self.order_book.track_levels();
+        let refused = match command {
+            Command::Place(order) => {
+                let order_id = order.id;
+                self.submit(order).err().map(|error| (order_id, error))
+            }
+            Command::Cancel { order_id } => self.cancel_order(order_id).err().map(|error| (order_id, error)),
+            Command::Amend { order_id, price, quantity, sequence } => {
+                self.amend_order(order_id, price, quantity, sequence).err().map(|error| (order_id, error))
+            }
+            Command::ExpireOrders { now } => {
+                self.expire_orders(now);
+                None
+            }
+            Command::PlaceOco { group_id, legs } => {
+                let order_id = legs.first().map_or(0, |leg| leg.id);
+                self.place_oco(group_id, legs).err().map(|error| (order_id, error))
+            }
+            Command::PlaceBracket { group_id, parent, take_profit, stop_loss } => {
+                let order_id = parent.id;
+                self.place_bracket(group_id, parent, take_profit, stop_loss).err().map(|error| (order_id, error))
+            }
+            Command::StartAuction => {
+                self.start_auction();
+                None
+            }
+            Command::Uncross => {
+                self.uncross();
+                None
+            }
+            Command::ClearBatch => {
+                self.clear_batch();
+                None
+            }
+            Command::Halt => {
+                self.halt();
+                None
+            }
+            Command::Resume => {
+                self.resume();
+                None
+            }
+        };
+
+        let mut events = self.events.take().unwrap_or_default();
+        if let Some((order_id, error)) = refused {
+            events.push(EngineEvent::CommandRejected { order_id, error });
         }
+        let scale = self.scale;
+        events.extend(self.order_book.take_level_changes().into_iter().map(|(side, price, quantity)| {
+            EngineEvent::BookLevelChanged { side, price: scale.price(price), quantity: scale.volume(quantity) }
+        }));
+        events
     }
 
     /// ------------------------
-    pub fn place_order(&mut self, order: Order) -> Order {
+    pub fn place_order(&mut self, order: Order) -> Result<Order, EngineError> {
+        let placed = self.submit(order)?;
+        Ok(self.scale.api_order(placed))
+    }
+
+    /// validate and place an order, leaving it in the book's representation
+    fn submit(&mut self, order: Order) -> Result<BookOrder, EngineError> {
+        // the event reports the order as it was sent, rather than converting it back
+        let accepted = self.events.is_some().then(|| order.clone());
+        let order = self.scale.book_order(order)?;
+        self.validate(&order)?;
+        if let Some(accepted) = accepted {
+            self.emit(|_| EngineEvent::OrderAccepted(accepted));
+        }
+        self.claim_id(order.id);
+        Ok(self.place(order))
+    }
+
+    /// an accepted order may be reusing the id of a finished one, whose trades
+    /// shouldn't be reported as its own, nor its place in a group taken over
+    fn claim_id(&mut self, order_id: u64) {
+        if self.config.id_reuse == IdReuse::AfterTerminal {
+            self.trade_history.forget_order(order_id);
+            if let Some(membership) = self.order_book.order(order_id).and_then(|o| o.group) {
+                self.leave_group(membership, order_id);
+            }
+        }
+    }
+
+    /// refuse orders the engine can't take at all, before anything is recorded
+    fn validate(&self, order: &BookOrder) -> Result<(), EngineError> {
+        if self.phase == TradingPhase::Halted {
+            return Err(EngineError::InstrumentHalted(order.instrument_id));
+        }
+        // every order the engine has seen stays in the book, working or not
+        if let Some(existing) = self.order_book.order(order.id) {
+            let reusable = self.config.id_reuse == IdReuse::AfterTerminal && existing.status.is_terminal();
+            if !reusable {
+                return Err(EngineError::DuplicateOrderId(order.id));
+            }
+        }
+        if order.quantity <= 0 {
+            return Err(EngineError::InvalidQuantity(self.scale.quantity(order.quantity)));
+        }
+        // a peg's price comes from the book, and market-type orders don't have one
+        let priced = matches!(order.order_type, OrderType::Limit | OrderType::StopLimit) && order.peg.is_none();
+        if priced && order.price <= 0 {
+            return Err(EngineError::InvalidPrice(self.scale.price(order.price)));
+        }
+        if let Some(stop_price) = order.stop_price.filter(|p| *p <= 0) {
+            return Err(EngineError::InvalidPrice(self.scale.price(stop_price)));
+        }
+        // pegged to the far touch with nothing holding it back, a bid would follow the
+        // offer up through every level
+        if let Some(peg) = order.peg.filter(|p| p.peg_type == PegType::Market) {
+            if peg.offset <= 0 && peg.limit_price.is_none() {
+                let reason = "a market peg needs an offset away from the touch or a limit price";
+                return Err(EngineError::UnsupportedOrder(reason.to_string()));
+            }
+        }
+        // the midpoint book crosses whole queues in arrival order and has no owner checks
+        if order.midpoint && (order.all_or_none || order.min_quantity.is_some() || order.stp_mode.is_some()) {
+            let reason = "midpoint orders can't be all-or-none or carry a min quantity or STP mode";
+            return Err(EngineError::UnsupportedOrder(reason.to_string()));
+        }
+        self.check_risk(order.quantity)
+    }
+
+    /// orders placed together can't share an id between themselves either
+    fn check_distinct_ids<'a>(orders: impl Iterator<Item = &'a BookOrder>) -> Result<(), EngineError> {
+        let mut seen = HashSet::new();
+        for order in orders {
+            if !seen.insert(order.id) {
+                return Err(EngineError::DuplicateOrderId(order.id));
+            }
+        }
+        Ok(())
+    }
+
+    /// per-instrument limits from the config
+    fn check_risk(&self, quantity: Lots) -> Result<(), EngineError> {
+        match self.max_order_quantity {
+            Some(max) if quantity > max => {
+                let (quantity, max) = (self.scale.quantity(quantity).normalize(), self.scale.quantity(max).normalize());
+                Err(EngineError::RiskRejected(format!("quantity {quantity} is over the {max} limit")))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn place(&mut self, order: BookOrder) -> BookOrder {
+        let placed = match order.order_type {
+            OrderType::Stop | OrderType::StopLimit => self.place_stop_order(order),
+            OrderType::Limit | OrderType::Market | OrderType::MarketToLimit => self.execute_order(order),
+        };
+        self.settle();
+
+        // the cascade above can reach any order: a stop it elected may have traded
+        // against it, a peg been repriced into it, or a group sibling cancelled it
+        self.order_book.order(placed.id).cloned().unwrap_or(placed)
+    }
+
+    /// run an order against the book; stops arrive here once elected
+    fn execute_order(&mut self, mut order: BookOrder) -> BookOrder {
+        if order.midpoint {
+            return self.place_midpoint_order(order);
+        }
+        if let Some(peg) = order.peg {
+            match self.peg_price(&order, peg) {
+                Some(price) => order.price = price,
+                None => return self.reject_order(order, StatusReason::NoPegReference),
+            }
+            self.pegs.insert(order.id);
+        }
+        if self.phase != TradingPhase::Continuous {
+            return self.place_auction_order(order);
+        }
+        if let Some(mode) = order.post_only {
+            match self.post_only_price(&order, mode) {
+                Some(price) => order.price = price,
+                None => return self.reject_order(order, StatusReason::PostOnlyWouldTake),
+            }
+        }
+        self.apply_slippage_band(&mut order);
+        // FOK is checked up front so a kill never touches the book
+        // (whatever self-trade prevention decrements it by no longer needs filling)
+        if order.time_in_force == TimeInForce::Fok {
+            let (fillable, decremented) = self.fillable_quantity(&order);
+            if fillable < Volume::from(order.remaining_quantity) - decremented {
+                return self.reject_order(order, StatusReason::FokUnfillable);
+            }
+        }
+        // same for a minimum quantity, which has to be met on arrival even if nothing crosses
+        if let Some(min_quantity) = order.min_quantity {
+            let (fillable, decremented) = self.fillable_quantity(&order);
+            if fillable < Volume::from(min_quantity).min(Volume::from(order.remaining_quantity) - decremented) {
+                return self.reject_order(order, StatusReason::MinQuantityUnfillable);
+            }
+        }
         match order.order_type {
-            OrderType::Limit  => self.place_limit_order(order),
-            OrderType::Market => self.place_market_order(order),
+            OrderType::Limit | OrderType::StopLimit => self.place_limit_order(order),
+            OrderType::Market | OrderType::MarketToLimit | OrderType::Stop => self.place_market_order(order),
         }
     }
 
+    /// during an auction orders only rest; anything that needs to execute immediately is killed
+    fn place_auction_order(&mut self, mut order: BookOrder) -> BookOrder {
+        let rests = matches!(order.order_type, OrderType::Limit | OrderType::StopLimit)
+            && !matches!(order.time_in_force, TimeInForce::Ioc | TimeInForce::Fok);
+        if !rests {
+            return self.reject_order(order, StatusReason::NotAcceptedInPhase);
+        }
+        self.track_expiry(&mut order);
+        self.rest(order)
+    }
+
     /// ------------------------
-    fn place_limit_order(&mut self, order: Order) -> Order {
-        let matched_order = self.match_order(&order);
-        if matched_order.remaining_quantity > Decimal::ZERO {
-            self.order_book.place_order(matched_order.clone());
-        } else {
-            self.order_book.orders.insert(matched_order.id, matched_order.clone());
+    fn place_stop_order(&mut self, mut order: BookOrder) -> BookOrder {
+        let Some(stop_price) = order.stop_price else {
+            return self.reject_order(order, StatusReason::MissingStopPrice);
+        };
+        self.track_expiry(&mut order);
+        self.order_book.record(order.clone());
+        self.triggers.insert(order.clone(), stop_price);
+        self.emit(|scale| EngineEvent::OrderParked(scale.api_order(order.clone())));
+        order
+    }
+
+    /// elect stops off the last trade price until nothing more triggers; every elected
+    /// order can trade and move the price again, so cascades resolve within one command
+    fn fire_triggers(&mut self) {
+        while !self.triggers.is_empty() {
+            let Some(last_price) = self.last_price else {
+                break;
+            };
+            for order in self.triggers.ratchet(last_price, self.tick_size) {
+                self.order_book.record(order);
+            }
+            let triggered = self.triggers.take_triggered(last_price);
+            if triggered.is_empty() {
+                break;
+            }
+            for order in triggered {
+                self.execute_order(order);
+            }
+        }
+    }
+
+    /// ------------------------
+    fn place_limit_order(&mut self, order: BookOrder) -> BookOrder {
+        let mut matched_order = self.match_order(&order);
+        // fully filled, or cancelled by self-trade prevention
+        if matched_order.remaining_quantity == 0 || matched_order.status == OrderStatus::Cancelled {
+            self.order_book.record(matched_order.clone());
+            return matched_order;
+        }
+
+        match matched_order.time_in_force {
+            TimeInForce::Ioc | TimeInForce::Fok => {
+                return self.kill_order(matched_order, StatusReason::ImmediateOrCancel);
+            }
+            TimeInForce::Gtc | TimeInForce::Gtd | TimeInForce::Day => {
+                self.track_expiry(&mut matched_order);
+                self.rest(matched_order.clone());
+            }
         }
         matched_order
     }
 
+    /// register GTD/DAY orders with the expiry sweep, resolving DAY to a concrete time
+    fn track_expiry(&mut self, order: &mut BookOrder) {
+        if order.time_in_force == TimeInForce::Day && order.expire_time.is_none() {
+            order.expire_time = order.timestamp.and_then(end_of_day);
+        }
+        if let Some(expire_time) = order.expire_time {
+            self.expiries.insert((expire_time, order.id));
+        }
+    }
+
     /// ------------------------
-    fn place_market_order(&mut self, mut order: Order) -> Order {
-        order = self.match_order(&order);
-        self.order_book.orders.insert(order.id, order.clone());
+    fn place_market_order(&mut self, order: BookOrder) -> BookOrder {
+        let mut order = self.match_order(&order);
+        if order.remaining_quantity == 0 || order.status == OrderStatus::Cancelled {
+            self.order_book.record(order.clone());
+            return order;
+        }
+
+        // market-to-limit rests what's left at its last fill, provided it got one
+        let filled = order.remaining_quantity < order.quantity;
+        let rests = order.order_type == OrderType::MarketToLimit
+            && filled
+            && !matches!(order.time_in_force, TimeInForce::Ioc | TimeInForce::Fok);
+        match self.last_price {
+            Some(price) if rests => {
+                order.order_type = OrderType::Limit;
+                order.price = price;
+                self.track_expiry(&mut order);
+                self.rest(order.clone());
+            }
+            // the book ran out or the protection price was reached; nothing is left working
+            _ => {
+                let reason = match self.get_best_matching_price(order.side) {
+                    Some(_) if order.protection_price.is_some() => StatusReason::ProtectionPrice,
+                    _ => StatusReason::NoLiquidity,
+                };
+                return self.kill_order(order, reason);
+            }
+        }
         order
     }
 
+    /// tighten a market-type order's protection price to the instrument's slippage band
+    /// around the touch it arrives at
+    fn apply_slippage_band(&self, order: &mut BookOrder) {
+        if !matches!(order.order_type, OrderType::Market | OrderType::MarketToLimit | OrderType::Stop) {
+            return;
+        }
+        let (Some(band), Some(touch)) = (self.config.max_slippage, self.get_best_matching_price(order.side)) else {
+            return;
+        };
+        let slippage = (Decimal::from(touch) * band / Decimal::ONE_HUNDRED).floor().to_i64().unwrap_or_default();
+        order.protection_price = Some(match (order.side, order.protection_price) {
+            (Side::Bid, Some(limit)) => limit.min(touch + slippage),
+            (Side::Bid, None) => touch + slippage,
+            (Side::Ask, Some(limit)) => limit.max(touch - slippage),
+            (Side::Ask, None) => touch - slippage,
+        });
+    }
+
+
+    /// price a post-only order may rest at without taking, or None if it has to be rejected
+    fn post_only_price(&self, order: &BookOrder, mode: PostOnly) -> Option<Ticks> {
+        if order.order_type != OrderType::Limit {
+            return None;
+        }
+        let opposite_best = match self.get_best_matching_price(order.side) {
+            Some(price) if self.should_match(order, price) => price,
+            _ => return Some(order.price),
+        };
+        match mode {
+            PostOnly::Reject => None,
+            PostOnly::Reprice => {
+                let price = match order.side {
+                    Side::Bid => opposite_best - self.tick_size,
+                    Side::Ask => opposite_best + self.tick_size,
+                };
+                (price > 0).then_some(price)
+            }
+        }
+    }
+
+    // --------
+    // midpoint dark book
+    // --------
+
+    /// dark orders go to the midpoint book and never touch the lit one
+    fn place_midpoint_order(&mut self, mut order: BookOrder) -> BookOrder {
+        let immediate = matches!(order.time_in_force, TimeInForce::Ioc | TimeInForce::Fok);
+        if immediate {
+            let fillable = match self.lit_midpoint() {
+                Some(mid) if self.phase == TradingPhase::Continuous => self.midpoint_book.fillable_quantity(&order, mid),
+                _ => 0,
+            };
+            let required = match order.time_in_force {
+                TimeInForce::Fok => Volume::from(order.remaining_quantity),
+                _ => 0,
+            };
+            if order.time_in_force == TimeInForce::Fok && fillable < required {
+                return self.reject_order(order, StatusReason::FokUnfillable);
+            }
+            if fillable == 0 {
+                return self.kill_order(order, StatusReason::ImmediateOrCancel);
+            }
+        } else {
+            self.track_expiry(&mut order);
+        }
+
+        self.order_book.record(order.clone());
+        self.midpoint_book.insert(order.clone());
+        self.emit(|scale| EngineEvent::OrderRested(scale.api_order(order.clone())));
+        self.cross_midpoint();
+
+        let mut placed = self.order_book.order(order.id).cloned().unwrap_or(order);
+        if immediate && placed.remaining_quantity > 0 {
+            self.midpoint_book.remove(placed.id);
+            placed = self.kill_order(placed, StatusReason::ImmediateOrCancel);
+        }
+        placed
+    }
+
+    /// halfway between the displayed best bid and best ask, rounded down to a whole tick
+    /// when the spread is an odd number of them
+    fn lit_midpoint(&self) -> Option<Ticks> {
+        let (bid, ask) = (self.order_book.displayed_best(Side::Bid)?, self.order_book.displayed_best(Side::Ask)?);
+        // halved before narrowing back, since the sum of two prices needn't fit in ticks
+        Some((Ticks::from(bid) as i128 + ask as i128).div_euclid(2) as Ticks)
+    }
+
+    /// trade whatever the midpoint book can at the current lit mid; returns whether anything traded
+    fn cross_midpoint(&mut self) -> bool {
+        if self.phase != TradingPhase::Continuous {
+            return false;
+        }
+        let Some(mid) = self.lit_midpoint() else {
+            return false;
+        };
+        let (trades, touched) = self.midpoint_book.cross(mid, self.scale);
+        let traded = !trades.is_empty();
+        if traded {
+            self.last_price = Some(mid);
+        }
+        for trade in trades {
+            self.record_trade(trade);
+        }
+        for order in touched {
+            if order.group.is_some() {
+                self.group_fills.push(order.id);
+            }
+            self.order_book.record(order);
+        }
+        traded
+    }
+
+
+    // --------
+    // pegged orders
+    // --------
+
+    /// where a pegged order should sit right now, or None if its reference side is empty
+    fn peg_price(&self, order: &BookOrder, peg: Peg<Ticks>) -> Option<Ticks> {
+        let reference = match (peg.peg_type, order.side) {
+            (PegType::Primary, Side::Bid) | (PegType::Market, Side::Ask) => self.unpegged_best(Side::Bid)?,
+            (PegType::Primary, Side::Ask) | (PegType::Market, Side::Bid) => self.unpegged_best(Side::Ask)?,
+            (PegType::Midpoint, side) => {
+                let sum = self.unpegged_best(Side::Bid)? + self.unpegged_best(Side::Ask)?;
+                // off-grid midpoints round away from the touch
+                match side {
+                    Side::Bid => scale::round_down(sum.div_euclid(2), self.tick_size),
+                    Side::Ask => scale::round_up(sum - sum.div_euclid(2), self.tick_size),
+                }
+            }
+        };
+        let price = match order.side {
+            Side::Bid => reference - peg.offset,
+            Side::Ask => reference + peg.offset,
+        };
+        let price = match (order.side, peg.limit_price) {
+            (Side::Bid, Some(limit)) => price.min(limit),
+            (Side::Ask, Some(limit)) => price.max(limit),
+            (_, None) => price,
+        };
+        (price > 0).then_some(price)
+    }
+
+    /// best displayed price on `side` ignoring pegged orders, so pegs never chase each other
+    fn unpegged_best(&self, side: Side) -> Option<Ticks> {
+        let slab = &self.order_book.slab;
+        self.order_book.find_level(side, |price, level: &Level| {
+            level.orders(slab).any(|o| o.peg.is_none() && o.is_displayed()).then_some(price)
+        })
+    }
+
+    /// move resting pegs onto their current price, oldest sequence first. a moved peg
+    /// loses its queue position and is matched again since it may now cross; trades can
+    /// shift the BBO, so this repeats until nothing moves.
+    fn reprice_pegs(&mut self) {
+        if self.phase != TradingPhase::Continuous {
+            return;
+        }
+        loop {
+            let mut pegged = Vec::new();
+            let order_book = &self.order_book;
+            self.pegs.retain(|&order_id| match order_book.resting_order(order_id) {
+                Some(order) => {
+                    pegged.push((order.sequence, order_id));
+                    true
+                }
+                None => false,
+            });
+            pegged.sort_unstable();
+
+            let mut moved = false;
+            for (_, order_id) in pegged {
+                // an earlier reprice in this pass may have traded against it
+                let Some(order) = self.order_book.resting_order(order_id) else {
+                    continue;
+                };
+                let Some(peg) = order.peg else {
+                    continue;
+                };
+                // with no reference the peg stays where it is
+                match self.peg_price(order, peg) {
+                    Some(price) if price != order.price => {}
+                    _ => continue,
+                }
+                let order = order.clone();
+                self.order_book.take(order_id);
+                self.execute_order(order);
+                moved = true;
+            }
+            self.fire_triggers();
+            if !moved {
+                break;
+            }
+        }
+    }
+
+    /// note what the command being applied did; the event is only built if there is one
+    fn emit(&mut self, event: impl FnOnce(Scale) -> EngineEvent) {
+        if let Some(events) = &mut self.events {
+            events.push(event(self.scale));
+        }
+    }
+
+    fn rest(&mut self, order: BookOrder) -> BookOrder {
+        let rested = self.order_book.rest(order);
+        self.emit(|scale| EngineEvent::OrderRested(scale.api_order(rested.clone())));
+        rested
+    }
+
+    /// give a trade its ids and keep it
+    fn record_trade(&mut self, mut trade: Trade) {
+        self.last_trade_id += 1;
+        trade.trade_id = self.last_trade_id;
+        trade.maker_execution_id = self.last_trade_id * 2 - 1;
+        trade.taker_execution_id = self.last_trade_id * 2;
+        for fill in Fill::sides(&trade) {
+            self.emit(|_| EngineEvent::Fill(fill));
+        }
+        self.trade_history.add_trade(trade);
+    }
+
+    /// ------------------------
+    fn kill_order(&mut self, order: BookOrder, reason: StatusReason) -> BookOrder {
+        self.finish_order(order, OrderStatus::Cancelled, reason)
+    }
+
+    fn reject_order(&mut self, order: BookOrder, reason: StatusReason) -> BookOrder {
+        self.finish_order(order, OrderStatus::Rejected, reason)
+    }
+
+    /// record a terminal state for an order that isn't (or is no longer) on the book
+    fn finish_order(&mut self, mut order: BookOrder, status: OrderStatus, reason: StatusReason) -> BookOrder {
+        order.status = status;
+        order.status_reason = Some(reason);
+        self.order_book.record(order.clone());
+        self.emit_finished(&order);
+        order
+    }
+
+    /// report an order that has just reached a terminal state other than filled, and
+    /// have the next settle take it out of its group
+    fn emit_finished(&mut self, order: &BookOrder) {
+        self.emit(|scale| match order.status {
+            OrderStatus::Rejected => EngineEvent::OrderRejected(scale.api_order(order.clone())),
+            OrderStatus::Expired => EngineEvent::OrderExpired(scale.api_order(order.clone())),
+            _ => EngineEvent::OrderCancelled(scale.api_order(order.clone())),
+        });
+        if let Some(membership) = order.group {
+            self.group_exits.push((order.id, membership));
+        }
+    }
+
+
+    /// when `expire_orders` next has something to do. an order that has since filled or
+    /// been cancelled can still hold the spot, and is passed over when it comes due.
+    pub fn next_expiry(&self) -> Option<DateTime<Utc>> {
+        self.expiries.first().map(|&(expire_time, _)| expire_time)
+    }
+
+    /// expire every GTD/DAY order whose expiry is at or before `now`
+    pub fn expire_orders(&mut self, now: DateTime<Utc>) -> Vec<Order> {
+        let mut expired = Vec::new();
+        while let Some(&(expire_time, order_id)) = self.expiries.first() {
+            if expire_time > now {
+                break;
+            }
+            self.expiries.pop_first();
+            // the id may since have been reused by an order with a different expiry
+            if self.order_book.order(order_id).and_then(|o| o.expire_time) != Some(expire_time) {
+                continue;
+            }
+            // the order may have filled or been cancelled since it rested
+            if let Ok(order) = self.withdraw(order_id, OrderStatus::Expired, StatusReason::ExpireTime) {
+                expired.push(self.scale.api_order(order));
+            }
+        }
+        expired
+    }
+
+
+    pub fn cancel_order(&mut self, order_id: u64) -> Result<Order, EngineError> {
+        let cancelled = self.withdraw(order_id, OrderStatus::Cancelled, StatusReason::CancelRequested)?;
+        Ok(self.scale.api_order(cancelled))
+    }
+
+    /// take a working order out of the engine for good, along with whatever that
+    /// means for its group
+    fn withdraw(&mut self, order_id: u64, status: OrderStatus, reason: StatusReason) -> Result<BookOrder, EngineError> {
+        let cancelled = self.cancel(order_id, status, reason)?;
+        self.settle();
+        Ok(cancelled)
+    }
 
-    pub fn cancel_order(&mut self, order_id: u64) -> Option<Order> {
+    fn cancel(&mut self, order_id: u64, status: OrderStatus, reason: StatusReason) -> Result<BookOrder, EngineError> {
 	// first get the order to ensure it exists & can be cancelled
-	let order = match self.order_book.get_order_status(order_id) {
-	    Some(order) if order.status != OrderStatus::Filled && order.status != OrderStatus::Cancelled => {
+	let order = match self.order_book.order(order_id) {
+	    Some(order) if !order.status.is_terminal() => {
 		let mut order = order.clone();
-		order.status = OrderStatus::Cancelled;
-		Some(order)
+		order.status = status;
+		order.status_reason = Some(reason);
+		order
 	    }
-	    _ => None,
-	}?;
+	    Some(order) => return Err(EngineError::OrderClosed { order_id, status: order.status }),
+	    None => return Err(EngineError::UnknownOrder(order_id)),
+	};
 
-	self.order_book.remove_order(order_id);
-	self.order_book.orders.insert(order_id, order.clone());
-	Some(order)
+	self.order_book.take(order_id);
+	if order.midpoint {
+	    self.midpoint_book.remove(order_id);
+	}
+	if let Some(stop_price) = order.stop_price {
+	    self.triggers.remove(order_id, order.side, stop_price);
+	}
+	self.order_book.record(order.clone());
+	self.emit_finished(&order);
+	Ok(order)
+    }
+
+
+    /// amend a resting order's price and/or total quantity.
+    /// shrinking the quantity keeps queue position; a price change or an increase
+    /// re-sequences the order to `sequence` and runs it through matching again.
+    /// `sequence` goes unused otherwise, see `amend_requeues`. a pegged order only takes
+    /// a new quantity.
+    pub fn amend_order(
+        &mut self,
+        order_id: u64,
+        price: Option<Decimal>,
+        quantity: Option<Decimal>,
+        sequence: u64,
+    ) -> Result<Order, EngineError> {
+        let (new_price, new_quantity, requeue) = self.plan_amend(order_id, price, quantity)?;
+        let scale = self.scale;
+        if !requeue {
+            let amended = self.order_book.update_resting(order_id, |resting| {
+                // take the reduction out of an iceberg's reserve before its visible slice
+                let filled = resting.quantity - resting.remaining_quantity;
+                let reduction = resting.quantity - new_quantity;
+                resting.hidden_quantity = (resting.hidden_quantity - reduction).max(0);
+                resting.quantity = new_quantity;
+                resting.remaining_quantity = new_quantity - filled;
+            });
+            let amended = amended.map(|order| scale.api_order(order.clone())).ok_or(EngineError::NotAmendable(order_id))?;
+            self.emit(|_| EngineEvent::OrderAmended(amended.clone()));
+            return Ok(amended);
+        }
+
+        let Some(mut amended) = self.order_book.take(order_id) else {
+            return Err(EngineError::NotAmendable(order_id));
+        };
+        let filled = amended.quantity - amended.remaining_quantity;
+        amended.price = new_price;
+        amended.quantity = new_quantity;
+        amended.remaining_quantity = new_quantity - filled;
+        amended.hidden_quantity = 0;
+        amended.sequence = sequence;
+        self.emit(|scale| EngineEvent::OrderAmended(scale.api_order(amended.clone())));
+        let amended = self.execute_order(amended);
+        self.settle();
+        let amended = self.order_book.order(order_id).cloned().unwrap_or(amended);
+        Ok(scale.api_order(amended))
+    }
+
+    /// whether `amend_order` would accept this amend and re-queue the order, so that
+    /// a caller only has to hand out a new sequence number when one is used
+    pub fn amend_requeues(&self, order_id: u64, price: Option<Decimal>, quantity: Option<Decimal>) -> bool {
+        self.plan_amend(order_id, price, quantity).is_ok_and(|(_, _, requeue)| requeue)
+    }
+
+    /// check an amend, returning the order's new price and quantity and whether it
+    /// loses its queue position
+    fn plan_amend(
+        &self,
+        order_id: u64,
+        price: Option<Decimal>,
+        quantity: Option<Decimal>,
+    ) -> Result<(Ticks, Lots, bool), EngineError> {
+        let Some(resting) = self.order_book.resting_order(order_id) else {
+            return Err(match self.order_book.order(order_id) {
+                Some(order) if order.status.is_terminal() => EngineError::OrderClosed { order_id, status: order.status },
+                Some(_) => EngineError::NotAmendable(order_id),
+                None => EngineError::UnknownOrder(order_id),
+            });
+        };
+        if self.phase == TradingPhase::Halted {
+            return Err(EngineError::InstrumentHalted(resting.instrument_id));
+        }
+        // a peg's price comes from the book, and the next settle would only move it back
+        if price.is_some() && resting.peg.is_some() {
+            return Err(EngineError::NotAmendable(order_id));
+        }
+        let scale = self.scale;
+        let price = price.map(|p| scale.ticks(p).ok_or(EngineError::InvalidPrice(p))).transpose()?;
+        let quantity = quantity.map(|q| scale.lots(q).ok_or(EngineError::InvalidQuantity(q))).transpose()?;
+        let filled = resting.quantity - resting.remaining_quantity;
+        let new_price = price.unwrap_or(resting.price);
+        let new_quantity = quantity.unwrap_or(resting.quantity);
+        if new_quantity <= filled {
+            return Err(EngineError::InvalidQuantity(scale.quantity(new_quantity)));
+        }
+        if new_price <= 0 {
+            return Err(EngineError::InvalidPrice(scale.price(new_price)));
+        }
+        self.check_risk(new_quantity)?;
+        let requeue = new_price != resting.price || new_quantity > resting.quantity;
+        Ok((new_price, new_quantity, requeue))
+    }
+
+
+    // --------
+    // order groups
+    // --------
+
+    /// place OCO legs under `group_id`; once any leg trades the rest are cancelled,
+    /// including legs that haven't been placed yet. nothing is placed unless every
+    /// leg is acceptable.
+    pub fn place_oco(&mut self, group_id: u64, legs: Vec<Order>) -> Result<Vec<Order>, EngineError> {
+        if self.groups.contains_key(&group_id) {
+            return Err(EngineError::DuplicateGroupId(group_id));
+        }
+        let legs = legs.into_iter().map(|leg| self.scale.book_order(leg)).collect::<Result<Vec<_>, _>>()?;
+        for leg in &legs {
+            self.validate(leg)?;
+        }
+        Self::check_distinct_ids(legs.iter())?;
+        for leg in &legs {
+            self.emit(|scale| EngineEvent::OrderAccepted(scale.api_order(leg.clone())));
+            self.claim_id(leg.id);
+        }
+        let members = legs.iter().map(|leg| leg.id).collect();
+        self.groups.insert(group_id, OrderGroup::oco(members));
+
+        let membership = GroupMembership { group_id, kind: GroupKind::Oco, role: GroupRole::Leg };
+        let placed = legs
+            .into_iter()
+            .map(|mut leg| {
+                leg.group = Some(membership);
+                let placed = if self.groups.contains_key(&group_id) {
+                    self.place(leg)
+                } else {
+                    self.kill_order(leg, StatusReason::GroupResolved)
+                };
+                self.scale.api_order(placed)
+            })
+            .collect();
+        Ok(placed)
+    }
+
+    /// place a bracket: `parent` goes to the book now, `take_profit` and `stop_loss` wait
+    /// until it has filled completely and then work as an OCO pair. the children keep the
+    /// size they were given, and a parent cancelled after a partial fill cancels them
+    /// too. nothing is placed unless all three are acceptable.
+    pub fn place_bracket(
+        &mut self,
+        group_id: u64,
+        parent: Order,
+        take_profit: Order,
+        stop_loss: Order,
+    ) -> Result<Vec<Order>, EngineError> {
+        if self.groups.contains_key(&group_id) {
+            return Err(EngineError::DuplicateGroupId(group_id));
+        }
+        let (parent, take_profit, stop_loss) =
+            (self.scale.book_order(parent)?, self.scale.book_order(take_profit)?, self.scale.book_order(stop_loss)?);
+        for order in [&parent, &take_profit, &stop_loss] {
+            self.validate(order)?;
+        }
+        Self::check_distinct_ids([&parent, &take_profit, &stop_loss].into_iter())?;
+        for order in [&parent, &take_profit, &stop_loss] {
+            self.emit(|scale| EngineEvent::OrderAccepted(scale.api_order(order.clone())));
+            self.claim_id(order.id);
+        }
+        let member = |mut order: BookOrder, role| {
+            order.group = Some(GroupMembership { group_id, kind: GroupKind::Bracket, role });
+            order
+        };
+        let parent = member(parent, GroupRole::Parent);
+        let mut children = vec![member(take_profit, GroupRole::TakeProfit), member(stop_loss, GroupRole::StopLoss)];
+        // parked children are still visible through get_order_status, and expire while they wait
+        for child in &mut children {
+            self.track_expiry(child);
+            self.order_book.record(child.clone());
+            self.emit(|scale| EngineEvent::OrderParked(scale.api_order(child.clone())));
+        }
+        self.groups.insert(group_id, OrderGroup::bracket(parent.id, children.clone()));
+
+        let parent = self.place(parent);
+        let mut placed = vec![self.scale.api_order(parent)];
+        placed.extend(children.iter().filter_map(|child| self.order_book.get_order_status(child.id)));
+        Ok(placed)
+    }
+
+    /// react to group members trading: OCO siblings are cancelled, and a bracket whose
+    /// parent is done either activates its children or drops them. members that finished
+    /// without trading then leave, which retires a group once none are left. returns
+    /// whether anything happened, since activated children may trade in turn.
+    fn settle_groups(&mut self) -> bool {
+        let traded = std::mem::take(&mut self.group_fills);
+        let mut acted = false;
+        for order_id in traded {
+            let Some(order) = self.order_book.order(order_id).cloned() else {
+                continue;
+            };
+            let Some(membership) = order.group else {
+                continue;
+            };
+            let Some(group) = self.groups.get(&membership.group_id) else {
+                continue;
+            };
+            match (group.kind, order.status) {
+                (GroupKind::Oco, _) => {
+                    self.cancel_in_group(membership, Some(order_id));
+                    acted = true;
+                }
+                (GroupKind::Bracket, OrderStatus::Filled) => {
+                    self.activate_bracket(membership.group_id);
+                    acted = true;
+                }
+                (GroupKind::Bracket, status) if status.is_terminal() => {
+                    self.cancel_in_group(membership, None);
+                    acted = true;
+                }
+                (GroupKind::Bracket, _) => {}
+            }
+        }
+        for (order_id, membership) in std::mem::take(&mut self.group_exits) {
+            if self.groups.contains_key(&membership.group_id) {
+                self.release_from_group(membership, order_id);
+                acted = true;
+            }
+        }
+        acted
+    }
+
+    /// what a member finishing without a fill means for its group
+    fn release_from_group(&mut self, membership: GroupMembership, order_id: u64) {
+        match membership.role {
+            // without its entry a bracket has nothing to protect
+            GroupRole::Parent => self.cancel_in_group(membership, None),
+            _ => self.leave_group(membership, order_id),
+        }
+    }
+
+    /// the bracket parent filled: its children go live as an OCO pair
+    fn activate_bracket(&mut self, group_id: u64) {
+        let Some(group) = self.groups.get_mut(&group_id) else {
+            return;
+        };
+        let children = std::mem::take(&mut group.parked);
+        group.kind = GroupKind::Oco;
+        group.members.clear();
+        for mut child in children {
+            // cancelled while it was parked
+            if self.order_book.order(child.id).is_some_and(|o| o.status.is_terminal()) {
+                continue;
+            }
+            if let Some(membership) = child.group.as_mut() {
+                membership.kind = GroupKind::Oco;
+            }
+            // due, but the sweep hasn't got to it yet
+            if child.expire_time.is_some_and(|expire_time| expire_time <= Utc::now()) {
+                self.finish_order(child, OrderStatus::Expired, StatusReason::ExpireTime);
+                continue;
+            }
+            if let Some(group) = self.groups.get_mut(&group_id) {
+                group.members.push(child.id);
+            }
+            match child.order_type {
+                OrderType::Stop | OrderType::StopLimit => self.place_stop_order(child),
+                _ => self.execute_order(child),
+            };
+        }
+        // neither child was left to work
+        if self.groups.get(&group_id).is_some_and(|group| group.members.is_empty()) {
+            self.groups.remove(&group_id);
+        }
+    }
+
+    /// retire the group `member` belongs to, cancelling every order still working in
+    /// it except `keep`, and any children still parked
+    fn cancel_in_group(&mut self, member: GroupMembership, keep: Option<u64>) {
+        let Some(group) = self.groups.remove(&member.group_id) else {
+            return;
+        };
+        for order_id in group.members {
+            // members that already filled or were cancelled are simply skipped
+            if Some(order_id) != keep {
+                let _ = self.cancel(order_id, OrderStatus::Cancelled, StatusReason::GroupResolved);
+            }
+        }
+        for child in group.parked {
+            if self.order_book.order(child.id).is_some_and(|o| !o.status.is_terminal()) {
+                self.kill_order(child, StatusReason::GroupResolved);
+            }
+        }
+    }
+
+    /// one order dropped out of its group on its own, working or parked; the others carry on
+    fn leave_group(&mut self, member: GroupMembership, order_id: u64) {
+        let Some(group) = self.groups.get_mut(&member.group_id) else {
+            return;
+        };
+        group.members.retain(|id| *id != order_id);
+        group.parked.retain(|child| child.id != order_id);
+        if group.members.is_empty() && group.parked.is_empty() {
+            self.groups.remove(&member.group_id);
+        }
+    }
+
+    /// everything one command can set off: stop elections, peg reprices, midpoint
+    /// crosses and group reactions, each of which may trade and set off the others
+    fn settle(&mut self) {
+        loop {
+            self.fire_triggers();
+            self.reprice_pegs();
+            let crossed = self.cross_midpoint();
+            if !self.settle_groups() && !crossed {
+                break;
+            }
+        }
+    }
+
+
+    // --------
+    // call auction
+    // --------
+
+    pub fn trading_phase(&self) -> TradingPhase {
+        self.phase
+    }
+
+    /// stop continuous matching; orders accumulate until `uncross`
+    pub fn start_auction(&mut self) {
+        self.set_phase(TradingPhase::Auction);
+    }
+
+    /// stop taking new orders and amends; cancels still go through
+    pub fn halt(&mut self) {
+        if self.phase != TradingPhase::Halted {
+            self.halted_from = self.phase;
+        }
+        self.set_phase(TradingPhase::Halted);
+    }
+
+    /// lift a halt, going back to the phase it interrupted. an interrupted auction
+    /// carries on and still has to `uncross`, so a crossed book never goes continuous.
+    pub fn resume(&mut self) {
+        if self.phase == TradingPhase::Halted {
+            self.set_phase(self.halted_from);
+            self.settle();
+        }
+    }
+
+    fn set_phase(&mut self, phase: TradingPhase) {
+        if self.phase != phase {
+            self.phase = phase;
+            self.emit(|_| EngineEvent::PhaseChanged(phase));
+        }
+    }
+
+    /// the phase an instrument trades in outside of auctions and halts
+    fn resting_phase(config: &InstrumentConfig) -> TradingPhase {
+        match config.batch_interval {
+            Some(_) => TradingPhase::Batch,
+            None => TradingPhase::Continuous,
+        }
+    }
+
+    /// price and volume the book would uncross at right now
+    pub fn indicative_uncross(&self) -> Option<Uncross> {
+        self.equilibrium().map(|(uncross, _)| self.scale.api_uncross(uncross))
     }
 
+    /// the uncross, along with the all-or-none orders left out of it. an all-or-none
+    /// order the uncross would only part-fill is dropped and the price worked out
+    /// again without it, until every order left in fills in full or not at all.
+    fn equilibrium(&self) -> Option<(Uncross<Ticks, Volume>, Vec<u64>)> {
+        let mut left_out = Vec::new();
+        loop {
+            let uncross = auction::equilibrium(
+                &self.auction_quantities(Side::Bid, &left_out),
+                &self.auction_quantities(Side::Ask, &left_out),
+                self.last_price,
+            )?;
+            let before = left_out.len();
+            for side in [Side::Bid, Side::Ask] {
+                self.part_filled_aon(side, uncross, &mut left_out);
+            }
+            if left_out.len() == before {
+                return Some((uncross, left_out));
+            }
+        }
+    }
+
+    /// open quantity at each price on `side`, less the orders left out of the auction
+    fn auction_quantities(&self, side: Side, left_out: &[u64]) -> BTreeMap<Ticks, Volume> {
+        let mut quantities = self.order_book.level_quantities(side);
+        for order in left_out.iter().filter_map(|id| self.order_book.resting_order(*id)) {
+            if order.side == side {
+                if let Some(quantity) = quantities.get_mut(&order.price) {
+                    *quantity -= Volume::from(order.remaining_quantity);
+                }
+            }
+        }
+        quantities.retain(|_, quantity| *quantity > 0);
+        quantities
+    }
+
+    /// add the all-or-none orders on `side` that `uncross` would part-fill to `left_out`,
+    /// sharing its volume out in the order `execute_uncross` fills in
+    fn part_filled_aon(&self, side: Side, uncross: Uncross<Ticks, Volume>, left_out: &mut Vec<u64>) {
+        let mut left = uncross.volume;
+        let mut part_filled = Vec::new();
+        self.order_book.walk(side, |price, level| {
+            let crosses = match side {
+                Side::Bid => price >= uncross.price,
+                Side::Ask => price <= uncross.price,
+            };
+            if !crosses {
+                return ControlFlow::Break(());
+            }
+            for order in level.orders(&self.order_book.slab).filter(|o| !left_out.contains(&o.id)) {
+                let fill = left.min(Volume::from(order.remaining_quantity));
+                if order.all_or_none && fill < Volume::from(order.remaining_quantity) {
+                    part_filled.push(order.id);
+                }
+                left -= fill;
+            }
+            ControlFlow::Continue(())
+        });
+        left_out.append(&mut part_filled);
+    }
 
-    /// --------
-    /// core matcher against the book
-    /// --------
+    /// end the auction: execute everything that crosses at the equilibrium price,
+    /// then go back to continuous matching (or batching, for FBA instruments)
+    pub fn uncross(&mut self) -> Option<Uncross> {
+        let uncross = self.equilibrium();
+        if let Some((uncross, left_out)) = &uncross {
+            self.execute_uncross(*uncross, left_out);
+        }
+        self.set_phase(Self::resting_phase(&self.config));
+        self.settle();
+        uncross.map(|(uncross, _)| self.scale.api_uncross(uncross))
+    }
 
-    fn get_best_matching_price(&self, side: Side) -> Option<Decimal> {
-        match side {
-            Side::Bid => self.order_book.asks.keys().next().cloned(),
-            Side::Ask => self.order_book.bids.keys().next_back().cloned(),
+    /// one frequent-batch-auction clear: a uniform-price uncross that stays in batch
+    /// mode. no-op outside of the batch phase.
+    pub fn clear_batch(&mut self) -> Option<Uncross> {
+        if self.phase != TradingPhase::Batch {
+            return None;
         }
+        let (uncross, left_out) = self.equilibrium()?;
+        self.execute_uncross(uncross, &left_out);
+        self.settle();
+        Some(self.scale.api_uncross(uncross))
     }
 
-    fn should_match(&self, order: &Order, price: Decimal) -> bool {
+    /// walk best bid against best ask at the single uncross price until its volume is done,
+    /// passing over the orders `left_out` of it. within a pair the earlier order is recorded
+    /// as the maker.
+    fn execute_uncross(&mut self, uncross: Uncross<Ticks, Volume>, left_out: &[u64]) {
+        self.emit(|scale| EngineEvent::Uncrossed(scale.api_uncross(uncross)));
+        // every trade in an uncross happens at the same moment
+        let timestamp = Some(Utc::now());
+        let mut left = uncross.volume;
+        while left > 0 {
+            // hidden orders take part in the uncross, so this goes by the raw top of book
+            let first = |side| {
+                self.order_book.find_level(side, |price, level| {
+                    let slab = &self.order_book.slab;
+                    level.keys(slab).find(|key| !left_out.contains(&slab[*key].order.id)).map(|key| (price, key))
+                })
+            };
+            let (Some((bid_price, bid_key)), Some((ask_price, ask_key))) = (first(Side::Bid), first(Side::Ask)) else {
+                break;
+            };
+            if bid_price < ask_price {
+                break;
+            }
+            let (bid, ask) = (&self.order_book.slab[bid_key].order, &self.order_book.slab[ask_key].order);
+
+            let fill_quantity = bid.remaining_quantity.min(ask.remaining_quantity);
+            let fill_quantity = left.min(Volume::from(fill_quantity)) as Lots;
+            let (maker, taker) = if bid.sequence <= ask.sequence { (bid, ask) } else { (ask, bid) };
+            let mut trade = Trade::new(
+                maker.id,
+                taker.id,
+                maker.sequence,
+                taker.sequence,
+                self.scale.price(uncross.price),
+                self.scale.quantity(fill_quantity),
+                taker.side,
+                taker.ingress_timestamp_ns,
+            );
+            trade.timestamp = timestamp;
+            let (bid_id, ask_id) = (bid.id, ask.id);
+            self.record_trade(trade);
+            self.last_price = Some(uncross.price);
+            left -= Volume::from(fill_quantity);
+
+            self.order_book.fill_resting(Side::Bid, bid_price, bid_key, fill_quantity);
+            self.order_book.fill_resting(Side::Ask, ask_price, ask_key, fill_quantity);
+            for order_id in [bid_id, ask_id] {
+                if self.order_book.order(order_id).is_some_and(|o| o.group.is_some()) {
+                    self.group_fills.push(order_id);
+                }
+            }
+        }
+    }
+
+
+    // --------
+    // core matcher against the book
+    // --------
+
+    fn get_best_matching_price(&self, side: Side) -> Option<Ticks> {
+        self.order_book.touch(side.opposite())
+    }
+
+    /// the next opposite level after `passed`, going away from the touch
+    fn next_matching_price(&self, side: Side, passed: Ticks) -> Option<Ticks> {
+        self.order_book.next_price(side.opposite(), passed)
+    }
+
+    fn should_match(&self, order: &BookOrder, price: Ticks) -> bool {
         match order.order_type {
-            OrderType::Market => true,
-            OrderType::Limit => match order.side {
+            OrderType::Market | OrderType::MarketToLimit | OrderType::Stop => match (order.side, order.protection_price) {
+                (_, None) => true,
+                (Side::Ask, Some(limit)) => price >= limit,
+                (Side::Bid, Some(limit)) => price <= limit,
+            },
+            OrderType::Limit | OrderType::StopLimit => match order.side {
                 Side::Ask => price >= order.price,
                 Side::Bid => price <= order.price,
             },
         }
     }
 
-    fn match_at_price_level(matched_order: &mut Order, price: Decimal, resting_orders: &mut VecDeque<Order>) -> VecDeque<Trade> {
+    /// how much of `order` could fill right now, stopping once it is fully covered, and
+    /// how much self-trade prevention would decrement it by on the way
+    fn fillable_quantity(&self, order: &BookOrder) -> (Volume, Volume) {
+        let (mut fillable, mut decremented): (Volume, Volume) = (0, 0);
+        let wanted = Volume::from(order.remaining_quantity);
+        self.order_book.walk(order.side.opposite(), |price, resting_orders| {
+            if fillable + decremented >= wanted || !self.should_match(order, price) {
+                return ControlFlow::Break(());
+            }
+            for resting in resting_orders.orders(&self.order_book.slab) {
+                let open = wanted - decremented - fillable;
+                if open <= 0 {
+                    break;
+                }
+                let resting_quantity = Volume::from(resting.remaining_quantity);
+                match Self::self_trade_mode(order, resting) {
+                    // the resting order is cancelled and matching carries on past it
+                    Some(StpMode::CancelOldest) => continue,
+                    Some(StpMode::DecrementAndCancel) if resting_quantity < open => {
+                        decremented += resting_quantity;
+                        continue;
+                    }
+                    // anything else cancels the taker here
+                    Some(_) => return ControlFlow::Break(()),
+                    None => {}
+                }
+                // an all-or-none order only counts if what's still needed takes all of it
+                if resting.all_or_none && resting_quantity > open {
+                    continue;
+                }
+                fillable += resting_quantity;
+            }
+            ControlFlow::Continue(())
+        });
+        (fillable, decremented)
+    }
+
+    /// the STP mode to apply if `taker` meeting `resting` would be a self-trade
+    fn self_trade_mode(taker: &BookOrder, resting: &BookOrder) -> Option<StpMode> {
+        match (taker.owner_id, resting.owner_id) {
+            (Some(taker_owner), Some(resting_owner)) if taker_owner == resting_owner => taker.stp_mode,
+            _ => None,
+        }
+    }
+
+    /// apply STP between the taker and the resting order at `key`; a cancelled
+    /// resting order is taken off the level
+    fn prevent_self_trade(
+        mode: StpMode,
+        matched_order: &mut BookOrder,
+        level: &mut Level,
+        slab: &mut Slab<Node>,
+        key: SlabKey,
+    ) {
+        let resting_order = &slab[key].order;
+        let taker_open = matched_order.remaining_quantity;
+        let resting_open = resting_order.remaining_quantity;
+        let (cancel_taker, cancel_resting, decrement) = match mode {
+            StpMode::CancelNewest => (true, false, 0),
+            StpMode::CancelOldest => (false, true, 0),
+            StpMode::CancelBoth => (true, true, 0),
+            StpMode::DecrementAndCancel => {
+                let decrement = taker_open.min(resting_open);
+                (taker_open == decrement, resting_open == decrement, decrement)
+            }
+        };
+
+        matched_order.stp_events.push(StpEvent {
+            resting_order_id: resting_order.id,
+            mode,
+            taker_quantity_cancelled: if cancel_taker { taker_open } else { decrement },
+            resting_quantity_cancelled: if cancel_resting { resting_open } else { decrement },
+        });
+
+        if cancel_taker {
+            matched_order.status = OrderStatus::Cancelled;
+            matched_order.status_reason = Some(StatusReason::SelfTradePrevention);
+        } else {
+            matched_order.quantity -= decrement;
+            matched_order.remaining_quantity -= decrement;
+        }
+
+        if cancel_resting {
+            level.unlink(slab, key);
+            let resting_order = &mut slab[key].order;
+            resting_order.status = OrderStatus::Cancelled;
+            resting_order.status_reason = Some(StatusReason::SelfTradePrevention);
+        } else if decrement > 0 {
+            let resting_order = &mut slab[key].order;
+            level.forget(resting_order);
+            // take it out of an iceberg's reserve first so the visible slice survives
+            resting_order.hidden_quantity = (resting_order.hidden_quantity - decrement).max(0);
+            resting_order.quantity -= decrement;
+            resting_order.remaining_quantity -= decrement;
+            level.count(resting_order);
+        }
+    }
+
+    /// run the policy over the orders at a level the taker may trade with, returning the
+    /// handle and fill of each allocation. all-or-none orders bigger than what the taker
+    /// has left are hidden from it so they don't hold up the queue behind them.
+    fn allocate_eligible(
+        policy: &dyn MatchingPolicy,
+        matched_order: &BookOrder,
+        level: &Level,
+        slab: &Slab<Node>,
+        skipped_aon: &[u64],
+    ) -> Vec<(SlabKey, Lots)> {
+        let left = matched_order.remaining_quantity;
+        let eligible = |order: &BookOrder| {
+            !order.all_or_none || (order.remaining_quantity <= left && !skipped_aon.contains(&order.id))
+        };
+        // a policy filling from the front never looks past the orders that cover the
+        // taker, so a small take from a deep level doesn't walk the whole queue
+        let front_only = policy.fills_from_front();
+        let mut covered: Volume = 0;
+        let mut keys = Vec::new();
+        let mut view = Vec::new();
+        for key in level.keys(slab) {
+            if front_only && covered >= Volume::from(left) {
+                break;
+            }
+            let order = &slab[key].order;
+            if eligible(order) {
+                covered += Volume::from(order.visible_quantity());
+                keys.push(key);
+                view.push(order);
+            }
+        }
+        policy
+            .allocate(left, &view)
+            .into_iter()
+            .map(|(idx, fill)| (keys[idx], fill))
+            .collect()
+    }
+
+    /// match against one level, updating the resting orders in place. ids of grouped
+    /// orders that traded are added to `group_fills`.
+    fn match_at_price_level(
+        policy: &dyn MatchingPolicy,
+        matched_order: &mut BookOrder,
+        price: Ticks,
+        level: &mut Level,
+        slab: &mut Slab<Node>,
+        group_fills: &mut Vec<u64>,
+        scale: Scale,
+    ) -> VecDeque<Trade> {
         let mut trades = VecDeque::new();
+        let mut skipped_aon: Vec<u64> = Vec::new();
 
-	// actually fill @ price level
-        while let Some(resting_order) = resting_orders.front_mut() {
-            if matched_order.remaining_quantity == Decimal::ZERO {
+        // each round lets the policy share out what is left; a round ends early when an
+        // allocation would be a self-trade, since STP changes what the rest of the level sees,
+        // or when it would part-fill an all-or-none order, which then sits the level out
+        while matched_order.remaining_quantity > 0 && matched_order.status != OrderStatus::Cancelled {
+            let allocations = Self::allocate_eligible(policy, matched_order, level, slab, &skipped_aon);
+            if allocations.is_empty() {
                 break;
             }
 
-            let fill_quantity = matched_order.remaining_quantity.min(resting_order.remaining_quantity);
+            let mut self_trade = None;
+            let mut touched = Vec::new();
+            for (key, fill_quantity) in allocations {
+                let resting_order = &mut slab[key].order;
+                if let Some(mode) = Self::self_trade_mode(matched_order, resting_order) {
+                    self_trade = Some((key, mode));
+                    break;
+                }
+                if resting_order.all_or_none && fill_quantity < resting_order.remaining_quantity {
+                    skipped_aon.push(resting_order.id);
+                    break;
+                }
+
+	        // ...create trades but delay recording them in book...
+                let mut trade = Trade::new(
+                    resting_order.id,
+                    matched_order.id,
+                    resting_order.sequence,
+                    matched_order.sequence,
+                    scale.price(price),
+                    scale.quantity(fill_quantity),
+                    matched_order.side,
+                    matched_order.ingress_timestamp_ns,
+                );
+                // stamp with the taker's time rather than the wall clock so replays are identical
+                trade.timestamp = matched_order.timestamp;
+                trades.push_back(trade);
 
-	    // ...create trades but delay recording them in book...
-            trades.push_back(Trade::new(
-                resting_order.id,
-                matched_order.id,
-                resting_order.sequence,
-                matched_order.sequence,
-                price,
-                fill_quantity,
-                matched_order.side,
-                matched_order.ingress_timestamp_ns,
-            ));
+	        // ... reflect the qty changes...
+                touched.push(key);
+                matched_order.remaining_quantity -= fill_quantity;
+                level.forget(resting_order);
+                resting_order.remaining_quantity -= fill_quantity;
+                resting_order.status = if resting_order.remaining_quantity == 0 {
+                    OrderStatus::Filled
+                } else {
+                    OrderStatus::PartiallyFilled
+                };
+                level.count(resting_order);
+            }
 
-	    // ... reflect the qty changes...
-            matched_order.remaining_quantity -= fill_quantity;
-            resting_order.remaining_quantity -= fill_quantity;
+            if let Some((key, mode)) = self_trade {
+                Self::prevent_self_trade(mode, matched_order, level, slab, key);
+            }
 
-	    // ...update status of resting order...
-            if resting_order.remaining_quantity == Decimal::ZERO {
-                resting_orders.pop_front();
-            } else {
-                resting_order.status = OrderStatus::PartiallyFilled;
+	    // ...drop filled orders, and send icebergs whose slice is used up to the back
+	    // of the queue with the next slice from their reserve
+            for key in touched {
+                let order = &slab[key].order;
+                if order.group.is_some() {
+                    group_fills.push(order.id);
+                }
+                if order.remaining_quantity == 0 {
+                    level.unlink(slab, key);
+                } else if order.visible_quantity() == 0 {
+                    level.unlink(slab, key);
+                    slab[key].order.replenish();
+                    level.push(slab, key);
+                }
             }
         }
 
         trades
     }
 
-    fn update_order_status(order: &Order) -> OrderStatus {
-        if order.remaining_quantity == Decimal::ZERO {
+    fn update_order_status(order: &BookOrder) -> OrderStatus {
+        if order.remaining_quantity == 0 {
             OrderStatus::Filled
         } else if order.remaining_quantity < order.quantity {
             OrderStatus::PartiallyFilled
@@ -130,34 +1472,51 @@ impl MatchingEngine {
         }
     }
 
-    fn match_order(&mut self, order: &Order) -> Order {
+    fn match_order(&mut self, order: &BookOrder) -> BookOrder {
         let mut matched_order = order.clone();
+        let stp_seen = matched_order.stp_events.len();
         let mut trades_to_record = VecDeque::new();
+        let mut group_fills = Vec::new();
+        // the last level left with only orders this taker can't trade with (all-or-none)
+        let mut passed = None;
+        let mut last_traded = None;
 
-        while matched_order.remaining_quantity > Decimal::ZERO {
-            let best_price = self.get_best_matching_price(order.side);
+        while matched_order.remaining_quantity > 0 && matched_order.status != OrderStatus::Cancelled {
+            let best_price = match passed {
+                Some(passed) => self.next_matching_price(order.side, passed),
+                None => self.get_best_matching_price(order.side),
+            };
 
             if let Some(price) = best_price {
                 if !self.should_match(&matched_order, price) {
                     break;
                 }
 
+                self.order_book.note_level(order.side.opposite(), price);
+                let (policy, scale) = (self.policy.as_ref(), self.scale);
+                let OrderBook { asks, bids, slab, .. } = &mut self.order_book;
                 let orders = match order.side {
-                    Side::Bid => self.order_book.asks.get_mut(&price),
-                    Side::Ask => self.order_book.bids.get_mut(&price),
+                    Side::Bid => asks.get_mut(price),
+                    Side::Ask => bids.get_mut(price),
                 };
 
                 if let Some(resting_orders) = orders {
 		    // actually fill orders @ price level
-                    let mut level_trades = Self::match_at_price_level(&mut matched_order, price, resting_orders);
+                    let mut level_trades =
+                        Self::match_at_price_level(policy, &mut matched_order, price, resting_orders, slab, &mut group_fills, scale);
+                    if !level_trades.is_empty() {
+                        last_traded = Some(price);
+                    }
                     trades_to_record.append(&mut level_trades);
 
 		    // ...and remove the price level if it's empty
                     if resting_orders.is_empty() {
                         match order.side {
-                            Side::Bid => { self.order_book.asks.remove(&price); },
-                            Side::Ask => { self.order_book.bids.remove(&price); },
+                            Side::Bid => self.order_book.asks.close(price),
+                            Side::Ask => self.order_book.bids.close(price),
                         };
+                    } else {
+                        passed = Some(price);
                     }
                 }
             } else {
@@ -167,25 +1526,46 @@ impl MatchingEngine {
 
         // batch record all trades from order
 	// BTW this would be a good use case for something like kafka or redis i think
+        if matched_order.group.is_some() && !trades_to_record.is_empty() {
+            self.group_fills.push(matched_order.id);
+        }
         for trade in trades_to_record {
-            self.trade_history.add_trade(trade);
+            self.record_trade(trade);
         }
+        if last_traded.is_some() {
+            self.last_price = last_traded;
+        }
+        self.group_fills.append(&mut group_fills);
 
-        matched_order.status = Self::update_order_status(&matched_order);
+        // self-trade prevention may have cancelled resting orders, or the taker itself
+        // and a cancelled resting order leaves its group the same way a cancel request does
+        for event in &matched_order.stp_events[stp_seen..] {
+            if let Some(resting) = self.order_book.order(event.resting_order_id) {
+                if resting.status == OrderStatus::Cancelled && resting.status_reason == Some(StatusReason::SelfTradePrevention) {
+                    let resting = resting.clone();
+                    self.emit_finished(&resting);
+                }
+            }
+        }
+        if matched_order.status != OrderStatus::Cancelled {
+            matched_order.status = Self::update_order_status(&matched_order);
+        } else {
+            self.emit_finished(&matched_order);
+        }
         matched_order
     }
 
-    ///
-    /// ------------------ Getter/passthru funcs
-    ///
+    //
+    // ------------------ Getter/passthru funcs
+    //
 
     /// current state of the order book
-    pub fn get_order_book(&self, depth: usize) -> (Vec<(Decimal, Decimal)>, Vec<(Decimal, Decimal)>) {
+    pub fn get_order_book(&self, depth: usize) -> (PriceLevels, PriceLevels) {
         self.order_book.get_order_book(depth)
     }
 
     /// status of a specific order statis by ID
-    pub fn get_order_status(&self, order_id: u64) -> Option<&Order> {
+    pub fn get_order_status(&self, order_id: u64) -> Option<Order> {
         self.order_book.get_order_status(order_id)
     }
 
@@ -212,4 +1592,22 @@ impl MatchingEngine {
 	}
     }
 
+    /// a trade by its id
+    pub fn get_trade(&self, trade_id: u64) -> Option<Trade> {
+        self.trade_history.get_trade(trade_id).cloned()
+    }
+
+    /// every trade an order took part in, oldest first
+    pub fn get_order_trades(&self, order_id: u64) -> Vec<Trade> {
+        self.trade_history.get_order_trades(order_id)
+    }
+
+}
+
+/// midnight UTC following `ts`, used as the expiry for DAY orders
+fn end_of_day(ts: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    ts.date_naive()
+        .succ_opt()
+        .and_then(|day| day.and_hms_opt(0, 0, 0))
+        .map(|midnight| midnight.and_utc())
 }
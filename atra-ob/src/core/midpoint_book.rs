@@ -0,0 +1,104 @@
+use std::collections::VecDeque;
+use super::scale::{BookOrder, Scale};
+use super::trade_history::Trade;
+use super::types::{OrderStatus, OrderType, Side, Ticks, Volume};
+
+/// dark orders that only ever trade with each other, at the midpoint of the lit BBO.
+/// a limit order's price is the worst midpoint it accepts; a market order takes any.
+/// queues are FIFO by arrival. all-or-none, min quantity and self-trade prevention
+/// don't apply here, and the engine refuses midpoint orders that ask for them.
+#[derive(Debug, Default)]
+pub struct MidpointBook {
+    bids: VecDeque<BookOrder>,
+    asks: VecDeque<BookOrder>,
+}
+
+impl MidpointBook {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// -------------
+    pub fn insert(&mut self, order: BookOrder) {
+        match order.side {
+            Side::Bid => self.bids.push_back(order),
+            Side::Ask => self.asks.push_back(order),
+        }
+    }
+
+    /// -------------
+    pub fn remove(&mut self, order_id: u64) -> Option<BookOrder> {
+        for queue in [&mut self.bids, &mut self.asks] {
+            if let Some(idx) = queue.iter().position(|o| o.id == order_id) {
+                return queue.remove(idx);
+            }
+        }
+        None
+    }
+
+    /// how much of `order` could trade at `mid` right now
+    pub fn fillable_quantity(&self, order: &BookOrder, mid: Ticks) -> Volume {
+        let contra = match order.side {
+            Side::Bid => &self.asks,
+            Side::Ask => &self.bids,
+        };
+        contra.iter().filter(|o| Self::accepts(o, mid)).map(|o| Volume::from(o.remaining_quantity)).sum()
+    }
+
+    /// trade everything that accepts `mid`, oldest first on both sides; the earlier
+    /// order of each pair is the maker. returns the trades and a copy of every order
+    /// that traded, as it stands afterwards.
+    pub fn cross(&mut self, mid: Ticks, scale: Scale) -> (Vec<Trade>, Vec<BookOrder>) {
+        let mut trades = Vec::new();
+        let mut touched: Vec<BookOrder> = Vec::new();
+        loop {
+            let bid_idx = self.bids.iter().position(|o| Self::accepts(o, mid));
+            let ask_idx = self.asks.iter().position(|o| Self::accepts(o, mid));
+            let (Some(bid_idx), Some(ask_idx)) = (bid_idx, ask_idx) else {
+                break;
+            };
+            let (bid, ask) = (&mut self.bids[bid_idx], &mut self.asks[ask_idx]);
+
+            let quantity = bid.remaining_quantity.min(ask.remaining_quantity);
+            let (maker, taker) = if bid.sequence <= ask.sequence { (&*bid, &*ask) } else { (&*ask, &*bid) };
+            let mut trade = Trade::new(
+                maker.id,
+                taker.id,
+                maker.sequence,
+                taker.sequence,
+                scale.price(mid),
+                scale.quantity(quantity),
+                taker.side,
+                taker.ingress_timestamp_ns,
+            );
+            trade.timestamp = taker.timestamp;
+            trades.push(trade);
+
+            for order in [&mut *bid, &mut *ask] {
+                order.remaining_quantity -= quantity;
+                order.status = if order.remaining_quantity == 0 {
+                    OrderStatus::Filled
+                } else {
+                    OrderStatus::PartiallyFilled
+                };
+                touched.retain(|o| o.id != order.id);
+                touched.push(order.clone());
+            }
+            if self.bids[bid_idx].remaining_quantity == 0 {
+                self.bids.remove(bid_idx);
+            }
+            if self.asks[ask_idx].remaining_quantity == 0 {
+                self.asks.remove(ask_idx);
+            }
+        }
+        (trades, touched)
+    }
+
+    fn accepts(order: &BookOrder, mid: Ticks) -> bool {
+        match (order.order_type, order.side) {
+            (OrderType::Limit, Side::Bid) => mid <= order.price,
+            (OrderType::Limit, Side::Ask) => mid >= order.price,
+            _ => true,
+        }
+    }
+}
@@ -1,8 +1,25 @@
 mod orderbook;
 mod matchingengine;
 mod trade_history;
+mod config;
+mod error;
+mod trigger_book;
+mod matching_policy;
+mod auction;
+mod order_group;
+mod midpoint_book;
+mod price_level;
+mod ladder;
+mod events;
+mod slab;
+mod scale;
 pub mod types;
 
+pub use auction::Uncross;
+pub use config::{IdReuse, InstrumentConfig, PriceLadder, DEFAULT_SCALE};
+pub use error::EngineError;
+pub use events::{Command, EngineEvent, Fill, Liquidity};
+pub use matching_policy::{Fifo, MatchingPolicy, ProRata, TopOrderProRata};
 pub use matchingengine::MatchingEngine;
 pub use orderbook::OrderBook;
 pub use types::*;
@@ -0,0 +1,20 @@
+use super::scale::BookOrder;
+use super::types::GroupKind;
+
+/// the live state of one OCO or bracket group
+#[derive(Debug)]
+pub(crate) struct OrderGroup {
+    pub kind: GroupKind,
+    pub members: Vec<u64>, // working orders, in placement order
+    pub parked: Vec<BookOrder>, // bracket children waiting on the parent to fill
+}
+
+impl OrderGroup {
+    pub fn oco(members: Vec<u64>) -> Self {
+        Self { kind: GroupKind::Oco, members, parked: Vec::new() }
+    }
+
+    pub fn bracket(parent_id: u64, children: Vec<BookOrder>) -> Self {
+        Self { kind: GroupKind::Bracket, members: vec![parent_id], parked: children }
+    }
+}
@@ -1,91 +1,333 @@
 use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::ops::ControlFlow;
 use rust_decimal::Decimal;
-use super::types::{Order, Side};
+use super::config::{InstrumentConfig, PriceLadder};
+use super::error::EngineError;
+use super::ladder::{DenseLadder, Ladder};
+use super::price_level::{Level, Node};
+use super::scale::{BookOrder, Scale};
+use super::slab::{Slab, SlabKey};
+use super::types::{Lots, Order, OrderStatus, PriceLevels, Side, Ticks, Volume};
 
-#[derive(Debug, Default)]
+#[derive(Debug)]
 pub struct OrderBook {
-    pub(crate) asks: BTreeMap<Decimal, VecDeque<Order>>,
-    pub(crate) bids: BTreeMap<Decimal, VecDeque<Order>>,
-    pub(crate) orders: HashMap<u64, Order>,
+    pub(crate) asks: Box<dyn Ladder>,
+    pub(crate) bids: Box<dyn Ladder>,
+    // every order the book knows about, resting or not. price levels are linked
+    // through it, so a resting order's state lives in exactly one place.
+    pub(crate) slab: Slab<Node>,
+    keys: HashMap<u64, SlabKey>,
+    scale: Scale,
+    // while tracking, each level changed so far with its displayed quantity before
+    // the first change
+    level_changes: Option<Vec<(Side, Ticks, Volume)>>,
+}
+
+impl Default for OrderBook {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl OrderBook {
+    /// a book laid out like the default instrument's
     pub fn new() -> Self {
-        Self::default()
+        Self::with_config(&InstrumentConfig::default()).expect("the default config is valid")
+    }
+
+    pub(crate) fn with_config(config: &InstrumentConfig) -> Result<Self, EngineError> {
+        let scale = Scale::of(config)?;
+        let ladder = || -> Result<Box<dyn Ladder>, EngineError> {
+            Ok(match config.price_ladder {
+                PriceLadder::Tree => Box::new(BTreeMap::new()),
+                PriceLadder::Dense { reference_price, ticks } => Box::new(DenseLadder::new(
+                    scale.config_ticks("reference_price", reference_price)?,
+                    scale.config_ticks("tick_size", config.tick_size)?,
+                    ticks,
+                )),
+            })
+        };
+        Ok(Self {
+            asks: ladder()?,
+            bids: ladder()?,
+            slab: Slab::default(),
+            keys: HashMap::new(),
+            scale,
+            level_changes: None,
+        })
     }
 
     /// -------------
-    pub fn place_order(&mut self, order: Order) -> Order {
+    pub fn place_order(&mut self, order: Order) -> Result<Order, EngineError> {
+        let order = self.scale.book_order(order)?;
+        // a second entry under the same id would orphan the first in its level
+        if self.resting_order(order.id).is_some() {
+            return Err(EngineError::DuplicateOrderId(order.id));
+        }
+        let rested = self.rest(order);
+        Ok(self.scale.api_order(rested))
+    }
+
+    /// add an order to the back of its level. the caller makes sure the id isn't
+    /// already resting; the engine re-rests orders it has just taken off the book.
+    pub(crate) fn rest(&mut self, mut order: BookOrder) -> BookOrder {
+        order.replenish();
 	let order_clone = order.clone();
-        let price_map = match order.side {
+        let (side, price) = (order.side, order.price);
+        self.note_level(side, price);
+        let key = self.record(order);
+        let price_map = match side {
             Side::Ask => &mut self.asks,
             Side::Bid => &mut self.bids,
         };
 
-        let orders = price_map.entry(order.price).or_insert_with(VecDeque::new);
-        orders.push_back(order.clone());
-        //orders.sort_unstable(); // this should maintain time priority - need to verify
-        self.orders.insert(order.id, order);
+        price_map.open(price).push(&mut self.slab, key);
 	order_clone
     }
 
+    /// store the latest state of an order that isn't resting, returning its handle
+    pub(crate) fn record(&mut self, order: BookOrder) -> SlabKey {
+        match self.keys.get(&order.id) {
+            Some(&key) => {
+                // a queued order has to be changed through its level to keep the totals right
+                debug_assert!(!self.slab[key].is_queued());
+                self.slab[key].order = order;
+                key
+            }
+            None => {
+                let id = order.id;
+                let key = self.slab.insert(Node::new(order));
+                self.keys.insert(id, key);
+                key
+            }
+        }
+    }
+
     /// -------------
     pub fn remove_order(&mut self, order_id: u64) -> Option<Order> {
-        if let Some(order) = self.orders.remove(&order_id) {
-            let price_map = match order.side {
+        self.take(order_id).map(|order| self.scale.api_order(order))
+    }
+
+    /// forget an order entirely, taking it off its level if it's resting
+    pub(crate) fn take(&mut self, order_id: u64) -> Option<BookOrder> {
+        let key = self.keys.remove(&order_id)?;
+        if self.slab[key].is_queued() {
+            let order = &self.slab[key].order;
+            let (side, price) = (order.side, order.price);
+            self.note_level(side, price);
+            let price_map = match side {
                 Side::Ask => &mut self.asks,
                 Side::Bid => &mut self.bids,
             };
-
-            if let Some(orders) = price_map.get_mut(&order.price) {
-                orders.retain(|o| o.id != order_id);
-                if orders.is_empty() {
-                    price_map.remove(&order.price);
+            if let Some(level) = price_map.get_mut(price) {
+                level.unlink(&mut self.slab, key);
+                if level.is_empty() {
+                    price_map.close(price);
                 }
             }
-            Some(order)
+        }
+        self.slab.remove(key).map(|node| node.order)
+    }
+
+    /// fill the order at `key` on a level outside of normal matching (auction
+    /// uncross), dropping it from the level once filled
+    pub(crate) fn fill_resting(&mut self, side: Side, price: Ticks, key: SlabKey, quantity: Lots) {
+        self.note_level(side, price);
+        let price_map = match side {
+            Side::Ask => &mut self.asks,
+            Side::Bid => &mut self.bids,
+        };
+        let Some(level) = price_map.get_mut(price) else {
+            return;
+        };
+
+        let order = &mut self.slab[key].order;
+        level.forget(order);
+        order.remaining_quantity -= quantity;
+        order.status = if order.remaining_quantity == 0 {
+            OrderStatus::Filled
         } else {
-            None
+            OrderStatus::PartiallyFilled
+        };
+        if order.visible_quantity() <= 0 {
+            order.replenish();
+        }
+        level.count(order);
+
+        if order.remaining_quantity == 0 {
+            level.unlink(&mut self.slab, key);
+            if level.is_empty() {
+                price_map.close(price);
+            }
         }
     }
 
-    /// current state of the order book up to a certain depth.
-    pub fn get_order_book(&self, depth: usize) -> (Vec<(Decimal, Decimal)>, Vec<(Decimal, Decimal)>) {
-        let bids = self.bids.iter()
-            .rev()
-            .take(depth)
-            .map(|(price, orders)| (*price, orders.iter().map(|order| order.remaining_quantity).sum()))
-            .collect();
+    /// an order, if it's resting on the book
+    pub(crate) fn resting_order(&self, order_id: u64) -> Option<&BookOrder> {
+        let node = &self.slab[*self.keys.get(&order_id)?];
+        node.is_queued().then_some(&node.order)
+    }
 
-        let asks = self.asks.iter()
-            .take(depth)
-            .map(|(price, orders)| (*price, orders.iter().map(|order| order.remaining_quantity).sum()))
-            .collect();
+    /// change a resting order in place without moving it in its queue
+    pub(crate) fn update_resting(&mut self, order_id: u64, update: impl FnOnce(&mut BookOrder)) -> Option<&BookOrder> {
+        let key = *self.keys.get(&order_id)?;
+        if !self.slab[key].is_queued() {
+            return None;
+        }
+        let (side, price) = (self.slab[key].order.side, self.slab[key].order.price);
+        self.note_level(side, price);
+        let order = &mut self.slab[key].order;
+        let price_map = match order.side {
+            Side::Ask => &mut self.asks,
+            Side::Bid => &mut self.bids,
+        };
+        let level = price_map.get_mut(order.price)?;
+        level.forget(order);
+        update(order);
+        level.count(order);
+        Some(order)
+    }
 
-        (bids, asks)
+    /// start keeping track of which levels change
+    pub(crate) fn track_levels(&mut self) {
+        self.level_changes = Some(Vec::new());
+    }
+
+    /// remember a level's displayed quantity before it's changed, if tracking
+    pub(crate) fn note_level(&mut self, side: Side, price: Ticks) {
+        let Some(changes) = &self.level_changes else {
+            return;
+        };
+        if changes.iter().any(|&(s, p, _)| s == side && p == price) {
+            return;
+        }
+        let ladder = match side {
+            Side::Ask => &self.asks,
+            Side::Bid => &self.bids,
+        };
+        let displayed = ladder.get(price).map_or(0, |level| level.displayed);
+        if let Some(changes) = &mut self.level_changes {
+            changes.push((side, price, displayed));
+        }
+    }
+
+    /// stop tracking, returning the displayed quantity now at every tracked level that
+    /// shows something different than before
+    pub(crate) fn take_level_changes(&mut self) -> Vec<(Side, Ticks, Volume)> {
+        let changes = self.level_changes.take().unwrap_or_default();
+        changes
+            .into_iter()
+            .filter_map(|(side, price, before)| {
+                let ladder = match side {
+                    Side::Ask => &self.asks,
+                    Side::Bid => &self.bids,
+                };
+                let displayed = ladder.get(price).map_or(0, |level| level.displayed);
+                (displayed != before).then_some((side, price, displayed))
+            })
+            .collect()
+    }
+
+    /// total open quantity at each price on `side`, hidden orders included
+    pub(crate) fn level_quantities(&self, side: Side) -> BTreeMap<Ticks, Volume> {
+        let mut quantities = BTreeMap::new();
+        self.walk(side, |price, level| {
+            quantities.insert(price, level.quantity);
+            ControlFlow::Continue(())
+        });
+        quantities
+    }
+
+    /// visit the levels on `side` from the touch outward until `visit` breaks
+    pub(crate) fn walk(&self, side: Side, mut visit: impl FnMut(Ticks, &Level) -> ControlFlow<()>) {
+        match side {
+            Side::Bid => self.bids.walk(false, &mut visit),
+            Side::Ask => self.asks.walk(true, &mut visit),
+        }
+    }
+
+    /// the first thing `find` picks out of the levels on `side`, from the touch outward
+    pub(crate) fn find_level<T>(&self, side: Side, mut find: impl FnMut(Ticks, &Level) -> Option<T>) -> Option<T> {
+        let mut found = None;
+        self.walk(side, |price, level| {
+            found = find(price, level);
+            match found {
+                Some(_) => ControlFlow::Break(()),
+                None => ControlFlow::Continue(()),
+            }
+        });
+        found
+    }
+
+    /// best price on `side`, hidden orders included
+    pub(crate) fn touch(&self, side: Side) -> Option<Ticks> {
+        match side {
+            Side::Bid => self.bids.highest(),
+            Side::Ask => self.asks.lowest(),
+        }
+    }
+
+    /// the next price on `side` after `passed`, going away from the touch
+    pub(crate) fn next_price(&self, side: Side, passed: Ticks) -> Option<Ticks> {
+        match side {
+            Side::Bid => self.bids.below(passed),
+            Side::Ask => self.asks.above(passed),
+        }
+    }
+
+    /// current state of the order book up to a certain depth. hidden orders aren't
+    /// counted, and levels holding only hidden orders aren't shown at all.
+    pub fn get_order_book(&self, depth: usize) -> (PriceLevels, PriceLevels) {
+        let shown = |side| {
+            let mut levels = PriceLevels::new();
+            self.walk(side, |price, level| {
+                if levels.len() >= depth {
+                    return ControlFlow::Break(());
+                }
+                if level.displayed > 0 {
+                    levels.push((self.scale.price(price), self.scale.volume(level.displayed)));
+                }
+                ControlFlow::Continue(())
+            });
+            levels
+        };
+
+        (shown(Side::Bid), shown(Side::Ask))
     }
 
     /// order status by id
-    pub fn get_order_status(&self, order_id: u64) -> Option<&Order> {
-        self.orders.get(&order_id)
+    pub fn get_order_status(&self, order_id: u64) -> Option<Order> {
+        self.order(order_id).map(|order| self.scale.api_order(order.clone()))
+    }
+
+    /// the book's own copy of an order, working or not
+    pub(crate) fn order(&self, order_id: u64) -> Option<&BookOrder> {
+        self.keys.get(&order_id).map(|&key| &self.slab[key].order)
     }
 
     /// get all orders at a specific price & side
     pub fn orders_at_price(&self, price: Decimal, side: Side) -> VecDeque<Order> {
+        let Some(price) = self.scale.ticks(price) else {
+            return VecDeque::new();
+        };
         match side {
-            Side::Ask => self.asks.get(&price),
-            Side::Bid => self.bids.get(&price),
+            Side::Ask => self.asks.get(price),
+            Side::Bid => self.bids.get(price),
         }
-        .map(|orders| orders.clone())
+        .map(|level| level.orders(&self.slab).map(|order| self.scale.api_order(order.clone())).collect())
         .unwrap_or_default()
     }
 
-    /// -------------
+    /// best displayed bid; hidden orders don't set the BBO
     pub fn best_bid(&self) -> Option<Decimal> {
-        self.bids.keys().next_back().cloned()
+        self.displayed_best(Side::Bid).map(|price| self.scale.price(price))
     }
-    /// -------------
+    /// best displayed ask
     pub fn best_ask(&self) -> Option<Decimal> {
-        self.asks.keys().next().cloned()
+        self.displayed_best(Side::Ask).map(|price| self.scale.price(price))
+    }
+
+    pub(crate) fn displayed_best(&self, side: Side) -> Option<Ticks> {
+        self.find_level(side, |price, level| (level.displayed > 0).then_some(price))
     }
 }
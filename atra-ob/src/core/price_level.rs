@@ -0,0 +1,120 @@
+use super::scale::BookOrder;
+use super::slab::{Slab, SlabKey};
+use super::types::Volume;
+
+/// an order in the book's slab, with its links into the queue at its price level
+#[derive(Debug)]
+pub(crate) struct Node {
+    pub(crate) order: BookOrder,
+    prev: Option<SlabKey>,
+    next: Option<SlabKey>,
+    queued: bool,
+}
+
+impl Node {
+    pub(crate) fn new(order: BookOrder) -> Self {
+        Self {
+            order,
+            prev: None,
+            next: None,
+            queued: false,
+        }
+    }
+
+    /// whether the order is sitting in a level's queue
+    pub(crate) fn is_queued(&self) -> bool {
+        self.queued
+    }
+}
+
+/// the queue at one price, kept as a doubly linked list threaded through the slab so
+/// an order leaves it in O(1). hidden orders always sit behind the displayed ones.
+#[derive(Debug, Default)]
+pub(crate) struct Level {
+    head: Option<SlabKey>,
+    tail: Option<SlabKey>,
+    first_hidden: Option<SlabKey>,
+    /// open quantity of every order in the queue, hidden ones included
+    pub(crate) quantity: Volume,
+    /// what the public book shows at this price
+    pub(crate) displayed: Volume,
+}
+
+impl Level {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.head.is_none()
+    }
+
+    /// handles in queue order
+    pub(crate) fn keys<'a>(&self, slab: &'a Slab<Node>) -> impl Iterator<Item = SlabKey> + 'a {
+        std::iter::successors(self.head, |&key| slab[key].next)
+    }
+
+    /// orders in queue order
+    pub(crate) fn orders<'a>(&self, slab: &'a Slab<Node>) -> impl Iterator<Item = &'a BookOrder> + 'a {
+        self.keys(slab).map(|key| &slab[key].order)
+    }
+
+    /// join the back of the queue; a displayed order goes in ahead of the first hidden one
+    pub(crate) fn push(&mut self, slab: &mut Slab<Node>, key: SlabKey) {
+        self.count(&slab[key].order);
+        let hidden = slab[key].order.hidden;
+        let (prev, next) = match self.first_hidden {
+            Some(first_hidden) if !hidden => (slab[first_hidden].prev, Some(first_hidden)),
+            _ => (self.tail, None),
+        };
+
+        let node = &mut slab[key];
+        node.prev = prev;
+        node.next = next;
+        node.queued = true;
+        match prev {
+            Some(prev) => slab[prev].next = Some(key),
+            None => self.head = Some(key),
+        }
+        match next {
+            Some(next) => slab[next].prev = Some(key),
+            None => self.tail = Some(key),
+        }
+        if hidden && self.first_hidden.is_none() {
+            self.first_hidden = Some(key);
+        }
+    }
+
+    /// take an order out of the queue, wherever it is
+    pub(crate) fn unlink(&mut self, slab: &mut Slab<Node>, key: SlabKey) {
+        let node = &mut slab[key];
+        let (prev, next) = (node.prev.take(), node.next.take());
+        node.queued = false;
+        self.forget(&slab[key].order);
+
+        match prev {
+            Some(prev) => slab[prev].next = next,
+            None => self.head = next,
+        }
+        match next {
+            Some(next) => slab[next].prev = prev,
+            None => self.tail = prev,
+        }
+        // hidden orders are all at the back, so the next one along is hidden too
+        if self.first_hidden == Some(key) {
+            self.first_hidden = next;
+        }
+    }
+
+    /// add a queued order's quantities to the totals; pairs with `forget` around any
+    /// change to an order while it's queued
+    pub(crate) fn count(&mut self, order: &BookOrder) {
+        self.quantity += Volume::from(order.remaining_quantity);
+        if order.is_displayed() {
+            self.displayed += Volume::from(order.visible_quantity());
+        }
+    }
+
+    pub(crate) fn forget(&mut self, order: &BookOrder) {
+        self.quantity -= Volume::from(order.remaining_quantity);
+        if order.is_displayed() {
+            self.displayed -= Volume::from(order.visible_quantity());
+        }
+    }
+}
@@ -0,0 +1,144 @@
+use std::convert::Infallible;
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use super::auction::Uncross;
+use super::config::InstrumentConfig;
+use super::error::EngineError;
+use super::types::{Lots, Order, Ticks, Volume};
+
+/// an order as the book and engine hold it, prices in ticks and sizes in lots
+pub(crate) type BookOrder = Order<i64>;
+
+/// the fixed point an instrument's prices and quantities are held at inside the
+/// book. everything crossing the engine's API goes through here.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Scale {
+    price: u32,    // decimal places in a tick
+    quantity: u32, // decimal places in a lot
+}
+
+/// finest scale there is room for: one whole unit at 10^18 still fits in an i64
+const MAX_SCALE: u32 = 18;
+
+/// largest order the book takes, in lots. a running total stops as soon as it covers
+/// an order, so one order's worth on top of another still fits in a lot count.
+const MAX_ORDER_LOTS: Lots = Lots::MAX / 2;
+
+impl Scale {
+    /// the config's scales, refused if a whole unit wouldn't fit in a tick or lot count
+    pub(crate) fn of(config: &InstrumentConfig) -> Result<Self, EngineError> {
+        for (name, scale) in [("price_scale", config.price_scale), ("quantity_scale", config.quantity_scale)] {
+            if scale > MAX_SCALE {
+                return Err(EngineError::InvalidConfig(format!("{name} {scale} is over the maximum of {MAX_SCALE}")));
+            }
+        }
+        Ok(Self { price: config.price_scale, quantity: config.quantity_scale })
+    }
+
+    /// None if `price` is finer than a tick or out of range
+    pub(crate) fn ticks(&self, price: Decimal) -> Option<Ticks> {
+        to_fixed(price, self.price)
+    }
+
+    /// None if `quantity` is finer than a lot or over `MAX_ORDER_LOTS`
+    pub(crate) fn lots(&self, quantity: Decimal) -> Option<Lots> {
+        to_fixed(quantity, self.quantity).filter(|lots| *lots <= MAX_ORDER_LOTS)
+    }
+
+    pub(crate) fn price(&self, ticks: Ticks) -> Decimal {
+        Decimal::new(ticks, self.price)
+    }
+
+    pub(crate) fn quantity(&self, lots: Lots) -> Decimal {
+        Decimal::new(lots, self.quantity)
+    }
+
+    /// a total across orders; a Decimal holds 96 bits, far more lots than can rest
+    pub(crate) fn volume(&self, volume: Volume) -> Decimal {
+        Decimal::from_i128_with_scale(volume, self.quantity)
+    }
+
+    /// a configured price in ticks, rounded down if it's finer than that. the config
+    /// is refused if the price doesn't fit at the scale.
+    pub(crate) fn config_ticks(&self, name: &str, price: Decimal) -> Result<Ticks, EngineError> {
+        floor_fixed(price, self.price).ok_or_else(|| {
+            EngineError::InvalidConfig(format!("{name} {price} doesn't fit at price_scale {}", self.price))
+        })
+    }
+
+    pub(crate) fn config_lots(&self, name: &str, quantity: Decimal) -> Result<Lots, EngineError> {
+        floor_fixed(quantity, self.quantity).ok_or_else(|| {
+            EngineError::InvalidConfig(format!("{name} {quantity} doesn't fit at quantity_scale {}", self.quantity))
+        })
+    }
+
+    pub(crate) fn book_order(&self, order: Order) -> Result<BookOrder, EngineError> {
+        order.try_convert(
+            |price| self.ticks(price).ok_or(EngineError::InvalidPrice(price)),
+            |quantity| self.lots(quantity).ok_or(EngineError::InvalidQuantity(quantity)),
+        )
+    }
+
+    /// by value so the idempotency key and STP events move across instead of being copied
+    pub(crate) fn api_order(&self, order: BookOrder) -> Order {
+        let converted = order.try_convert(
+            |ticks| Ok::<_, Infallible>(self.price(ticks)),
+            |lots| Ok(self.quantity(lots)),
+        );
+        match converted {
+            Ok(order) => order,
+            Err(never) => match never {},
+        }
+    }
+
+    pub(crate) fn api_uncross(&self, uncross: Uncross<Ticks, Volume>) -> Uncross {
+        Uncross {
+            price: self.price(uncross.price),
+            volume: self.volume(uncross.volume),
+            imbalance: self.volume(uncross.imbalance),
+        }
+    }
+}
+
+/// `price` moved down onto a multiple of `tick`
+pub(crate) fn round_down(price: Ticks, tick: Ticks) -> Ticks {
+    price - price.rem_euclid(tick)
+}
+
+/// `price` moved up onto a multiple of `tick`
+pub(crate) fn round_up(price: Ticks, tick: Ticks) -> Ticks {
+    round_down(price + tick - 1, tick)
+}
+
+fn to_fixed(value: Decimal, scale: u32) -> Option<i64> {
+    if value.is_zero() {
+        return Some(0);
+    }
+    let mut value = value;
+    if value.scale() > scale {
+        // extra decimal places are fine as long as they're all zeros
+        value = value.normalize();
+        if value.scale() > scale {
+            return None;
+        }
+    }
+    let factor = *POWERS_OF_TEN.get((scale - value.scale()) as usize)?;
+    i64::try_from(value.mantissa()).ok()?.checked_mul(factor)
+}
+
+const POWERS_OF_TEN: [i64; MAX_SCALE as usize + 1] = {
+    let mut powers = [1; 19];
+    let mut i = 1;
+    while i < powers.len() {
+        powers[i] = powers[i - 1] * 10;
+        i += 1;
+    }
+    powers
+};
+
+/// `value` at `scale` decimal places, rounded down. None if it doesn't fit.
+pub(crate) fn floor_fixed(value: Decimal, scale: u32) -> Option<i64> {
+    let factor = *POWERS_OF_TEN.get(scale as usize)?;
+    Decimal::from(factor).checked_mul(value)?.floor().to_i64()
+}
+
@@ -0,0 +1,64 @@
+use std::ops::{Index, IndexMut};
+
+/// handle to a slot in a `Slab`
+pub(crate) type SlabKey = usize;
+
+/// arena with stable handles; slots freed by `remove` are handed out again by later
+/// inserts, so handles stay small and the backing vec doesn't grow with churn
+#[derive(Debug)]
+pub(crate) struct Slab<T> {
+    entries: Vec<Option<T>>,
+    free: Vec<SlabKey>,
+}
+
+impl<T> Default for Slab<T> {
+    fn default() -> Self {
+        Self {
+            entries: Vec::new(),
+            free: Vec::new(),
+        }
+    }
+}
+
+impl<T> Slab<T> {
+    pub(crate) fn insert(&mut self, value: T) -> SlabKey {
+        match self.free.pop() {
+            Some(key) => {
+                self.entries[key] = Some(value);
+                key
+            }
+            None => {
+                self.entries.push(Some(value));
+                self.entries.len() - 1
+            }
+        }
+    }
+
+    pub(crate) fn remove(&mut self, key: SlabKey) -> Option<T> {
+        let value = self.entries.get_mut(key)?.take()?;
+        self.free.push(key);
+        Some(value)
+    }
+
+    pub(crate) fn get(&self, key: SlabKey) -> Option<&T> {
+        self.entries.get(key)?.as_ref()
+    }
+
+    pub(crate) fn get_mut(&mut self, key: SlabKey) -> Option<&mut T> {
+        self.entries.get_mut(key)?.as_mut()
+    }
+}
+
+impl<T> Index<SlabKey> for Slab<T> {
+    type Output = T;
+
+    fn index(&self, key: SlabKey) -> &T {
+        self.get(key).expect("vacant slab slot")
+    }
+}
+
+impl<T> IndexMut<SlabKey> for Slab<T> {
+    fn index_mut(&mut self, key: SlabKey) -> &mut T {
+        self.get_mut(key).expect("vacant slab slot")
+    }
+}
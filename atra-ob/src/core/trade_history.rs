@@ -1,4 +1,4 @@
-use std::collections::VecDeque;
+use std::collections::{HashMap, VecDeque};
 use chrono::{DateTime, Utc};
 use rust_decimal::Decimal;
 use crate::core::Side;
@@ -6,6 +6,9 @@ use crate::core::Side;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Trade {
+    pub trade_id: u64,           // counts up from 1 per instrument, 0 until the engine records it
+    pub maker_execution_id: u64, // one per side: 2 * trade_id - 1 for the maker, 2 * trade_id for the taker
+    pub taker_execution_id: u64,
     pub maker_order_id: u64,
     pub taker_order_id: u64,
     pub maker_sequence: u64,
@@ -18,6 +21,8 @@ pub struct Trade {
 }
 
 impl Trade {
+    /// a trade with no timestamp; the matcher stamps it with the time it goes by
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         maker_order_id: u64,
         taker_order_id: u64,
@@ -29,6 +34,9 @@ impl Trade {
         ingress_timestamp_ns: Option<u64>,
     ) -> Self {
         Self {
+            trade_id: 0,
+            maker_execution_id: 0,
+            taker_execution_id: 0,
             maker_order_id,
             taker_order_id,
             maker_sequence,
@@ -36,7 +44,7 @@ impl Trade {
             price,
             quantity,
             side,
-            timestamp: Some(Utc::now()),
+            timestamp: None,
             ingress_timestamp_ns,
         }
     }
@@ -44,20 +52,58 @@ impl Trade {
 
 pub struct TxnHistory {
     trades: VecDeque<Trade>,
+    previous: Vec<[Option<usize>; 2]>, // per trade, the position of the maker's and the taker's trade before it
+    last_by_order: HashMap<u64, usize>, // order id -> position of its latest trade
+}
+
+impl Default for TxnHistory {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl TxnHistory {
     pub fn new() -> Self {
 	Self {
-	    trades: VecDeque::with_capacity(1024)
+	    trades: VecDeque::with_capacity(1024),
+	    previous: Vec::with_capacity(1024),
+	    last_by_order: HashMap::new(),
 	}
     }
 
     /// ------------------
     pub fn add_trade(&mut self, trade: Trade) {
+        let position = self.trades.len();
+        let maker = self.last_by_order.insert(trade.maker_order_id, position);
+        let taker = self.last_by_order.insert(trade.taker_order_id, position);
+        self.previous.push([maker, taker]);
 	self.trades.push_back(trade);
     }
 
+    /// a trade by its id. ids count up by one per trade, so this is an offset from the first.
+    pub fn get_trade(&self, trade_id: u64) -> Option<&Trade> {
+        let position = trade_id.checked_sub(self.trades.front()?.trade_id)?;
+        self.trades.get(usize::try_from(position).ok()?).filter(|trade| trade.trade_id == trade_id)
+    }
+
+    /// every trade the order now holding `order_id` took part in, on either side, oldest first
+    pub fn get_order_trades(&self, order_id: u64) -> Vec<Trade> {
+        let mut positions = Vec::new();
+        let mut next = self.last_by_order.get(&order_id).copied();
+        while let Some(position) = next {
+            positions.push(position);
+            let side = usize::from(self.trades[position].maker_order_id != order_id);
+            next = self.previous[position][side];
+        }
+        positions.iter().rev().map(|&position| self.trades[position].clone()).collect()
+    }
+
+    /// start afresh for an order id that is being reused, leaving the earlier
+    /// order's trades to the trade log alone
+    pub(crate) fn forget_order(&mut self, order_id: u64) {
+        self.last_by_order.remove(&order_id);
+    }
+
     /// ------------------
     pub fn get_trades(&self) -> Vec<Trade> {
 	self.trades.iter().cloned().collect()
@@ -0,0 +1,122 @@
+use std::collections::{BTreeMap, VecDeque};
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use super::scale::{self, BookOrder};
+use super::types::{OrderType, Side, Ticks, Trail};
+
+/// parked stop orders waiting on the last trade price, kept apart from the lit book
+#[derive(Debug, Default)]
+pub struct TriggerBook {
+    buy_stops: BTreeMap<Ticks, VecDeque<BookOrder>>,  // elected when last >= stop
+    sell_stops: BTreeMap<Ticks, VecDeque<BookOrder>>, // elected when last <= stop
+}
+
+impl TriggerBook {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// -------------
+    pub fn insert(&mut self, order: BookOrder, stop_price: Ticks) {
+        let stops = match order.side {
+            Side::Bid => &mut self.buy_stops,
+            Side::Ask => &mut self.sell_stops,
+        };
+        stops.entry(stop_price).or_default().push_back(order);
+    }
+
+    /// -------------
+    pub fn remove(&mut self, order_id: u64, side: Side, stop_price: Ticks) -> Option<BookOrder> {
+        let stops = match side {
+            Side::Bid => &mut self.buy_stops,
+            Side::Ask => &mut self.sell_stops,
+        };
+        let orders = stops.get_mut(&stop_price)?;
+        let idx = orders.iter().position(|o| o.id == order_id)?;
+        let order = orders.remove(idx);
+        if orders.is_empty() {
+            stops.remove(&stop_price);
+        }
+        order
+    }
+
+    /// pull every stop elected by `last_price`.
+    /// buys come out lowest stop first and sells highest stop first (the order the
+    /// price move crossed them in), FIFO within a stop price, buys before sells.
+    pub fn take_triggered(&mut self, last_price: Ticks) -> Vec<BookOrder> {
+        let mut triggered = Vec::new();
+
+        while let Some(entry) = self.buy_stops.first_entry() {
+            if *entry.key() > last_price {
+                break;
+            }
+            triggered.extend(entry.remove());
+        }
+
+        while let Some(entry) = self.sell_stops.last_entry() {
+            if *entry.key() < last_price {
+                break;
+            }
+            triggered.extend(entry.remove());
+        }
+
+        triggered
+    }
+
+    /// move trailing stops behind `last_price`: sell stops only ever go up and buy stops
+    /// only ever go down, each to `trail` away from the last trade (rounded outward to
+    /// `tick_size`). a stop-limit's limit moves with it. returns the orders that moved.
+    pub fn ratchet(&mut self, last_price: Ticks, tick_size: Ticks) -> Vec<BookOrder> {
+        let mut moves = Vec::new();
+        for (side, stops) in [(Side::Bid, &self.buy_stops), (Side::Ask, &self.sell_stops)] {
+            for (stop_price, orders) in stops {
+                for order in orders {
+                    let Some(trail) = order.trail else {
+                        continue;
+                    };
+                    let distance = trail_distance(trail, last_price);
+                    let trailed = match side {
+                        Side::Bid => scale::round_up(last_price + distance, tick_size),
+                        Side::Ask => scale::round_down(last_price - distance, tick_size),
+                    };
+                    let tighter = match side {
+                        Side::Bid => trailed < *stop_price,
+                        Side::Ask => trailed > *stop_price,
+                    };
+                    if tighter {
+                        moves.push((order.id, side, *stop_price, trailed));
+                    }
+                }
+            }
+        }
+
+        let mut moved = Vec::new();
+        for (order_id, side, from, to) in moves {
+            let Some(mut order) = self.remove(order_id, side, from) else {
+                continue;
+            };
+            if order.order_type == OrderType::StopLimit {
+                order.price += to - from;
+            }
+            order.stop_price = Some(to);
+            self.insert(order.clone(), to);
+            moved.push(order);
+        }
+        moved
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buy_stops.is_empty() && self.sell_stops.is_empty()
+    }
+}
+
+/// how far from `last_price` the stop trails; a percentage is rounded up to whole ticks
+fn trail_distance(trail: Trail<Ticks>, last_price: Ticks) -> Ticks {
+    match trail {
+        Trail::Amount(amount) => amount,
+        Trail::Percent(percent) => {
+            let distance = Decimal::from(last_price) * percent / Decimal::ONE_HUNDRED;
+            distance.ceil().to_i64().unwrap_or(Ticks::MAX)
+        }
+    }
+}
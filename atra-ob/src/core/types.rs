@@ -1,6 +1,7 @@
 use chrono::{DateTime, Utc};
 use rust_decimal::Decimal;
 use std::cmp::Ordering;
+use std::ops::{Add, AddAssign, Sub, SubAssign};
 
 //
 // enums
@@ -12,10 +13,23 @@ pub enum Side {
     Ask,
 }
 
+impl Side {
+    /// the side an order on this one trades against
+    pub fn opposite(&self) -> Side {
+        match self {
+            Side::Bid => Side::Ask,
+            Side::Ask => Side::Bid,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum OrderType {
     Limit,
     Market,
+    MarketToLimit, // market order whose unfilled part rests as a limit at its last fill price
+    Stop,      // market order once the last trade reaches stop_price
+    StopLimit, // limit order once the last trade reaches stop_price
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -24,6 +38,161 @@ pub enum OrderStatus {
     PartiallyFilled,
     Filled,
     Cancelled,
+    Rejected, // refused on arrival, never worked
+    Expired,  // GTD/DAY time ran out
+}
+
+impl OrderStatus {
+    /// nothing more can happen to the order
+    pub fn is_terminal(&self) -> bool {
+        matches!(self, Self::Filled | Self::Cancelled | Self::Rejected | Self::Expired)
+    }
+}
+
+/// why an order was rejected, cancelled or expired
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatusReason {
+    // rejected
+    PostOnlyWouldTake,
+    FokUnfillable,
+    MinQuantityUnfillable,
+    NotAcceptedInPhase,  // needs to execute immediately, but the book is in an auction
+    NoPegReference,      // nothing on the side the peg tracks
+    MissingStopPrice,
+    // cancelled
+    CancelRequested,
+    ImmediateOrCancel,   // IOC/FOK remainder
+    NoLiquidity,         // market remainder with nothing left to take
+    ProtectionPrice,     // market remainder past its protection price
+    SelfTradePrevention,
+    GroupResolved,       // another member of its OCO/bracket group settled it
+    // expired
+    ExpireTime,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TradingPhase {
+    #[default]
+    Continuous,
+    Auction, // orders rest without matching until the uncross
+    Batch,   // frequent batch auction: orders rest and clear together on a timer
+    Halted,  // no new orders or amends; cancels still accepted
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TimeInForce {
+    #[default]
+    Gtc, // rests until filled or cancelled
+    Ioc, // fill what crosses now, cancel the rest
+    Fok, // fill completely now or not at all
+    Gtd, // rests until expire_time
+    Day, // rests until end of the (UTC) trading day
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PostOnly {
+    Reject,  // cancel the order if it would cross on arrival
+    Reprice, // move it one tick behind the opposite best instead
+}
+
+/// self-trade prevention, applied by the incoming order when it meets a resting
+/// order from the same owner
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StpMode {
+    CancelNewest,       // cancel the incoming order's remainder
+    CancelOldest,       // cancel the resting order and keep matching
+    CancelBoth,
+    DecrementAndCancel, // shrink the larger by the smaller's size and cancel the smaller
+}
+
+/// how the orders in a group are linked
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GroupKind {
+    Oco,     // a fill on any leg cancels the others
+    Bracket, // an entry whose take-profit/stop-loss children go live (as an OCO pair) once it fills
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GroupRole {
+    Leg,        // OCO leg
+    Parent,     // bracket entry
+    TakeProfit, // bracket child
+    StopLoss,   // bracket child
+}
+
+/// which group an order belongs to and what part it plays there
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GroupMembership {
+    pub group_id: u64,
+    pub kind: GroupKind,
+    pub role: GroupRole,
+}
+
+/// how far a trailing stop sits behind the last trade price
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Trail<A = Decimal> {
+    Amount(A),
+    Percent(Decimal), // of the last trade price, 1.5 = 1.5%
+}
+
+impl Trail {
+    /// price distance from `last_price`
+    pub fn distance(&self, last_price: Decimal) -> Decimal {
+        match *self {
+            Trail::Amount(amount) => amount,
+            Trail::Percent(percent) => last_price * percent / Decimal::ONE_HUNDRED,
+        }
+    }
+}
+
+/// what a pegged order tracks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PegType {
+    Primary,  // same-side best (bid pegs to best bid)
+    Market,   // opposite-side best (bid pegs to best ask)
+    Midpoint, // halfway between best bid and best ask
+}
+
+/// a limit order whose price follows the BBO. `offset` moves it away from the
+/// reference, less aggressive for positive values; `limit_price` caps how far it chases.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Peg<A = Decimal> {
+    pub peg_type: PegType,
+    pub offset: A,
+    pub limit_price: Option<A>,
+}
+
+/// what self-trade prevention did to the incoming order and one resting order
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StpEvent<A = Decimal> {
+    pub resting_order_id: u64,
+    pub mode: StpMode,
+    pub taker_quantity_cancelled: A,
+    pub resting_quantity_cancelled: A,
+}
+
+/// (price, aggregate quantity) per level, best price first
+pub type PriceLevels = Vec<(Decimal, Decimal)>;
+
+/// a price inside the book, in units of 10^-price_scale
+pub type Ticks = i64;
+/// a quantity inside the book, in units of 10^-quantity_scale
+pub type Lots = i64;
+/// a sum of lots across orders, wide enough that no number of them overflows it
+pub type Volume = i128;
+
+/// what an order carries its prices and quantities as: `Decimal` at the API,
+/// fixed-point integers (ticks and lots) inside the book
+pub trait Amount: Copy + Ord + Add<Output = Self> + Sub<Output = Self> + AddAssign + SubAssign {
+    const ZERO: Self;
+}
+
+impl Amount for Decimal {
+    const ZERO: Self = Decimal::ZERO;
+}
+
+impl Amount for i64 {
+    const ZERO: Self = 0;
 }
 
 //
@@ -31,28 +200,46 @@ pub enum OrderStatus {
 //
 
 #[derive(Debug, Clone, PartialEq, Eq)] // def dont want copy
-pub struct Order {
+pub struct Order<A = Decimal> {
     pub id: u64,
     pub instrument_id: u32,
     pub sequence: u64,
-    pub price: Decimal,
-    pub quantity: Decimal,
-    pub remaining_quantity: Decimal,
+    pub price: A,
+    pub quantity: A,
+    pub remaining_quantity: A,
     pub side: Side,
     pub order_type: OrderType,
     pub status: OrderStatus,
+    pub status_reason: Option<StatusReason>, // set once rejected, cancelled or expired
+    pub time_in_force: TimeInForce,
+    pub expire_time: Option<DateTime<Utc>>,
+    pub post_only: Option<PostOnly>,
+    pub stop_price: Option<A>,
+    pub trail: Option<Trail<A>>,           // makes a stop a trailing stop
+    pub protection_price: Option<A>,       // worst price a market-type order may sweep to
+    pub min_quantity: Option<A>,           // rejected unless at least this fills on arrival
+    pub all_or_none: bool,                 // as a maker, fill only in full; never displayed
+    pub group: Option<GroupMembership>,    // OCO/bracket membership
+    pub hidden: bool,                      // rests on the lit book without being displayed
+    pub midpoint: bool,                    // rests in the dark midpoint book instead
+    pub display_quantity: Option<A>,       // iceberg peak size
+    pub hidden_quantity: A,                // iceberg reserve not currently shown
+    pub owner_id: Option<u64>,             // account/participant, what STP keys on
+    pub stp_mode: Option<StpMode>,
+    pub stp_events: Vec<StpEvent<A>>,
+    pub peg: Option<Peg<A>>,
     pub timestamp: Option<DateTime<Utc>>,
     pub ingress_timestamp_ns: Option<u64>,
     pub idempotency_key: Option<String>,
 }
 
-impl Order {
+impl<A: Amount> Order<A> {
     pub fn new(
         id: u64,
         instrument_id: u32,
         sequence: u64,
-        price: Decimal,
-        quantity: Decimal,
+        price: A,
+        quantity: A,
         side: Side,
         order_type: OrderType,
     ) -> Self {
@@ -66,15 +253,120 @@ impl Order {
             side,
             order_type,
             status: OrderStatus::Pending,
+            status_reason: None,
+            time_in_force: TimeInForce::Gtc,
+            expire_time: None,
+            post_only: None,
+            stop_price: None,
+            trail: None,
+            protection_price: None,
+            min_quantity: None,
+            all_or_none: false,
+            group: None,
+            hidden: false,
+            midpoint: false,
+            display_quantity: None,
+            hidden_quantity: A::ZERO,
+            owner_id: None,
+            stp_mode: None,
+            stp_events: Vec::new(),
+            peg: None,
             timestamp: Some(Utc::now()),
             ingress_timestamp_ns: None,
             idempotency_key: None,
         }
     }
+
+    /// quantity shown on the book; an iceberg only shows its current slice
+    pub fn visible_quantity(&self) -> A {
+        self.remaining_quantity - self.hidden_quantity
+    }
+
+    /// whether the order shows on the lit book at all. all-or-none interest only trades
+    /// against a size that takes all of it, so it isn't shown either; otherwise the book
+    /// could read as crossed while nothing on it can trade.
+    pub fn is_displayed(&self) -> bool {
+        !self.hidden && !self.all_or_none
+    }
+
+    /// show the next display_quantity slice of an iceberg out of its hidden reserve
+    pub fn replenish(&mut self) {
+        if let Some(display) = self.display_quantity.filter(|d| *d > A::ZERO) {
+            self.hidden_quantity = (self.remaining_quantity - display).max(A::ZERO);
+        }
+    }
 }
 
+impl<A: Copy> Order<A> {
+    /// the same order with its prices (`price`) and sizes (`quantity`) carried in
+    /// another representation
+    pub(crate) fn try_convert<B, E>(
+        self,
+        price: impl Fn(A) -> Result<B, E>,
+        quantity: impl Fn(A) -> Result<B, E>,
+    ) -> Result<Order<B>, E> {
+        let trail = match self.trail {
+            Some(Trail::Amount(amount)) => Some(Trail::Amount(price(amount)?)),
+            Some(Trail::Percent(percent)) => Some(Trail::Percent(percent)),
+            None => None,
+        };
+        let peg = match self.peg {
+            Some(peg) => Some(Peg {
+                peg_type: peg.peg_type,
+                offset: price(peg.offset)?,
+                limit_price: peg.limit_price.map(&price).transpose()?,
+            }),
+            None => None,
+        };
+        let stp_events = self
+            .stp_events
+            .into_iter()
+            .map(|event| {
+                Ok(StpEvent {
+                    resting_order_id: event.resting_order_id,
+                    mode: event.mode,
+                    taker_quantity_cancelled: quantity(event.taker_quantity_cancelled)?,
+                    resting_quantity_cancelled: quantity(event.resting_quantity_cancelled)?,
+                })
+            })
+            .collect::<Result<_, E>>()?;
+
+        Ok(Order {
+            id: self.id,
+            instrument_id: self.instrument_id,
+            sequence: self.sequence,
+            price: price(self.price)?,
+            quantity: quantity(self.quantity)?,
+            remaining_quantity: quantity(self.remaining_quantity)?,
+            side: self.side,
+            order_type: self.order_type,
+            status: self.status,
+            status_reason: self.status_reason,
+            time_in_force: self.time_in_force,
+            expire_time: self.expire_time,
+            post_only: self.post_only,
+            stop_price: self.stop_price.map(&price).transpose()?,
+            trail,
+            protection_price: self.protection_price.map(&price).transpose()?,
+            min_quantity: self.min_quantity.map(&quantity).transpose()?,
+            all_or_none: self.all_or_none,
+            group: self.group,
+            hidden: self.hidden,
+            midpoint: self.midpoint,
+            display_quantity: self.display_quantity.map(&quantity).transpose()?,
+            hidden_quantity: quantity(self.hidden_quantity)?,
+            owner_id: self.owner_id,
+            stp_mode: self.stp_mode,
+            stp_events,
+            peg,
+            timestamp: self.timestamp,
+            ingress_timestamp_ns: self.ingress_timestamp_ns,
+            idempotency_key: self.idempotency_key,
+        })
+    }
+}
 
-impl Ord for Order {
+impl<A: Amount> Ord for Order<A> {
     fn cmp(&self, other: &Self) -> Ordering {
 	match self.price.cmp(&other.price) {
 	    Ordering::Equal => self.sequence.cmp(&other.sequence),
@@ -86,7 +378,7 @@ impl Ord for Order {
     }
 }
 
-impl PartialOrd for Order {
+impl<A: Amount> PartialOrd for Order<A> {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         Some(self.cmp(other))
     }
@@ -1,6 +1,7 @@
 pub mod core;
 pub mod api;
 
+#[allow(non_camel_case_types, clippy::large_enum_variant)] // generated code
 pub mod proto {
     tonic::include_proto!("orderbook");
 }
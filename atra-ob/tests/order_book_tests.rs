@@ -1,6 +1,7 @@
 use rust_decimal_macros::dec;
-//use chrono::Utc;
-use atra_ob::core::{OrderBook, Order, Side, OrderType, OrderStatus, MatchingEngine};
+use chrono::{Duration, Utc};
+use atra_ob::core::{OrderBook, Order, Side, OrderType, OrderStatus, MatchingEngine, TimeInForce, PostOnly, InstrumentConfig, StpMode, ProRata, TopOrderProRata, TradingPhase, Peg, PegType, Trail, GroupRole, StatusReason, EngineError, IdReuse, PriceLadder, Command, EngineEvent, Fill, Liquidity};
+use std::sync::Arc;
 
 
 fn create_test_order(id: u64, price: rust_decimal::Decimal, quantity: rust_decimal::Decimal, side: Side, order_type: OrderType) -> Order {
@@ -13,10 +14,10 @@ fn test_place_order() {
     let mut book = OrderBook::new();
 
     let order = create_test_order(1, dec!(100.0), dec!(10.0), Side::Bid, OrderType::Limit);
-    let result = book.place_order(order.clone());
+    let result = book.place_order(order.clone()).unwrap();
 
     assert_eq!(result.status, OrderStatus::Pending);
-    assert_eq!(book.get_order_status(1), Some(&result));
+    assert_eq!(book.get_order_status(1), Some(result));
 }
 
 
@@ -24,12 +25,13 @@ fn test_place_order() {
 fn test_duplicate_order_id() {
     let mut book = OrderBook::new();
 
-    book.place_order(create_test_order(1, dec!(100.0), dec!(10.0), Side::Bid, OrderType::Limit));
+    let first = book.place_order(create_test_order(1, dec!(100.0), dec!(10.0), Side::Bid, OrderType::Limit)).unwrap();
 
     let result = book.place_order(create_test_order(1, dec!(101.0), dec!(20.0), Side::Ask, OrderType::Limit));
 
-    assert_eq!(result.status, OrderStatus::Pending);
-    assert_eq!(book.get_order_status(1), Some(&result));
+    assert_eq!(result, Err(EngineError::DuplicateOrderId(1)));
+    assert_eq!(book.get_order_status(1), Some(first));
+    assert_eq!(book.get_order_book(10), (vec![(dec!(100.0), dec!(10.0))], vec![]));
 }
 
 
@@ -37,10 +39,10 @@ fn test_duplicate_order_id() {
 fn test_best_bid_ask() {
     let mut book = OrderBook::new();
 
-    book.place_order(create_test_order(1, dec!(98.0), dec!(10.0), Side::Bid, OrderType::Limit));
-    book.place_order(create_test_order(2, dec!(99.0), dec!(10.0), Side::Bid, OrderType::Limit));
-    book.place_order(create_test_order(3, dec!(101.0), dec!(10.0), Side::Ask, OrderType::Limit));
-    book.place_order(create_test_order(4, dec!(102.0), dec!(10.0), Side::Ask, OrderType::Limit));
+    book.place_order(create_test_order(1, dec!(98.0), dec!(10.0), Side::Bid, OrderType::Limit)).unwrap();
+    book.place_order(create_test_order(2, dec!(99.0), dec!(10.0), Side::Bid, OrderType::Limit)).unwrap();
+    book.place_order(create_test_order(3, dec!(101.0), dec!(10.0), Side::Ask, OrderType::Limit)).unwrap();
+    book.place_order(create_test_order(4, dec!(102.0), dec!(10.0), Side::Ask, OrderType::Limit)).unwrap();
 
     assert_eq!(book.best_bid(), Some(dec!(99.0)));
     assert_eq!(book.best_ask(), Some(dec!(101.0)));
@@ -54,8 +56,8 @@ fn test_orders_at_price() {
     let order1 = create_test_order(1, dec!(100.0), dec!(10.0), Side::Bid, OrderType::Limit);
     let order2 = create_test_order(2, dec!(100.0), dec!(20.0), Side::Bid, OrderType::Limit);
 
-    book.place_order(order1.clone());
-    book.place_order(order2.clone());
+    book.place_order(order1.clone()).unwrap();
+    book.place_order(order2.clone()).unwrap();
 
     let orders = book.orders_at_price(dec!(100.0), Side::Bid);
     assert_eq!(orders.len(), 2);
@@ -67,8 +69,8 @@ fn test_orders_at_price() {
 fn test_matching_creates_trade() {
     let mut book = MatchingEngine::new();
 
-    book.place_order(create_test_order(1, dec!(100.0), dec!(10.0), Side::Bid, OrderType::Limit));
-    book.place_order(create_test_order(2, dec!(100.0), dec!(5.0), Side::Ask, OrderType::Limit));
+    book.place_order(create_test_order(1, dec!(100.0), dec!(10.0), Side::Bid, OrderType::Limit)).unwrap();
+    book.place_order(create_test_order(2, dec!(100.0), dec!(5.0), Side::Ask, OrderType::Limit)).unwrap();
 
     let trades = book.get_trade_history(Some(10));
     assert_eq!(trades.len(), 1);
@@ -87,9 +89,9 @@ fn test_multiple_trades() {
     let mut book = MatchingEngine::new();
 
     // multiple resting buy orders vs large matching sell order
-    book.place_order(create_test_order(1, dec!(100.0), dec!(10.0), Side::Bid, OrderType::Limit));
-    book.place_order(create_test_order(2, dec!(101.0), dec!(10.0), Side::Bid, OrderType::Limit));
-    book.place_order(create_test_order(3, dec!(100.0), dec!(15.0), Side::Ask, OrderType::Limit));
+    book.place_order(create_test_order(1, dec!(100.0), dec!(10.0), Side::Bid, OrderType::Limit)).unwrap();
+    book.place_order(create_test_order(2, dec!(101.0), dec!(10.0), Side::Bid, OrderType::Limit)).unwrap();
+    book.place_order(create_test_order(3, dec!(100.0), dec!(15.0), Side::Ask, OrderType::Limit)).unwrap();
 
     let trades = book.get_trade_history(Some(10));
     assert_eq!(trades.len(), 2);
@@ -110,8 +112,8 @@ fn test_trade_history_limit() {
     let mut book = MatchingEngine::new();
 
     for i in 1..=5 {
-        book.place_order(create_test_order(i*2-1, dec!(100.0), dec!(10.0), Side::Bid, OrderType::Limit));
-        book.place_order(create_test_order(i*2, dec!(100.0), dec!(10.0), Side::Ask, OrderType::Limit));
+        book.place_order(create_test_order(i*2-1, dec!(100.0), dec!(10.0), Side::Bid, OrderType::Limit)).unwrap();
+        book.place_order(create_test_order(i*2, dec!(100.0), dec!(10.0), Side::Ask, OrderType::Limit)).unwrap();
     }
 
     // check only 3
@@ -129,8 +131,8 @@ fn test_market_order_trades() {
     let mut book = MatchingEngine::new();
 
     // resting sell order fills market buy
-    book.place_order(create_test_order(1, dec!(100.0), dec!(10.0), Side::Ask, OrderType::Limit));
-    book.place_order(create_test_order(2, dec!(0.0), dec!(5.0), Side::Bid, OrderType::Market));
+    book.place_order(create_test_order(1, dec!(100.0), dec!(10.0), Side::Ask, OrderType::Limit)).unwrap();
+    book.place_order(create_test_order(2, dec!(0.0), dec!(5.0), Side::Bid, OrderType::Market)).unwrap();
 
     let trades = book.get_trade_history(Some(10));
     assert_eq!(trades.len(), 1);
@@ -148,8 +150,8 @@ fn test_get_all_trades() {
     let mut book = MatchingEngine::new();
 
     for i in 1..=3 {
-        book.place_order(create_test_order(i*2-1, dec!(100.0), dec!(10.0), Side::Bid, OrderType::Limit));
-        book.place_order(create_test_order(i*2, dec!(100.0), dec!(10.0), Side::Ask, OrderType::Limit));
+        book.place_order(create_test_order(i*2-1, dec!(100.0), dec!(10.0), Side::Bid, OrderType::Limit)).unwrap();
+        book.place_order(create_test_order(i*2, dec!(100.0), dec!(10.0), Side::Ask, OrderType::Limit)).unwrap();
     }
 
     // all trades
@@ -164,14 +166,14 @@ fn test_price_time_priority() {
     let mut book = MatchingEngine::new();
 
     // orders at same price ==> time priority should apply
-    book.place_order(create_test_order(1, dec!(100.0), dec!(10.0), Side::Bid, OrderType::Limit));
-    book.place_order(create_test_order(2, dec!(100.0), dec!(10.0), Side::Bid, OrderType::Limit));
+    book.place_order(create_test_order(1, dec!(100.0), dec!(10.0), Side::Bid, OrderType::Limit)).unwrap();
+    book.place_order(create_test_order(2, dec!(100.0), dec!(10.0), Side::Bid, OrderType::Limit)).unwrap();
 
     // higher price order - should get matched first despite being later in time
-    book.place_order(create_test_order(3, dec!(101.0), dec!(10.0), Side::Bid, OrderType::Limit));
+    book.place_order(create_test_order(3, dec!(101.0), dec!(10.0), Side::Bid, OrderType::Limit)).unwrap();
 
     // sell order that will match against all three buy orders
-    book.place_order(create_test_order(4, dec!(100.0), dec!(30.0), Side::Ask, OrderType::Limit));
+    book.place_order(create_test_order(4, dec!(100.0), dec!(30.0), Side::Ask, OrderType::Limit)).unwrap();
 
     let trades = book.get_trade_history(None);
     assert_eq!(trades.len(), 3, "Should have three trades");
@@ -203,8 +205,8 @@ fn test_replay_is_deterministic() {
         create_test_order(4, dec!(100.0), dec!(7.0), Side::Ask, OrderType::Limit),
     ];
     for order in &stream {
-        run1.place_order(order.clone());
-        run2.place_order(order.clone());
+        run1.place_order(order.clone()).unwrap();
+        run2.place_order(order.clone()).unwrap();
     }
     assert_eq!(run1.get_order_book(10), run2.get_order_book(10));
     assert_eq!(run1.get_trade_history(None), run2.get_trade_history(None));
@@ -217,10 +219,1377 @@ fn test_duplicate_sequence_orders_still_fifo_by_arrival() {
     first.sequence = 42;
     let mut second = create_test_order(2, dec!(100.0), dec!(5.0), Side::Bid, OrderType::Limit);
     second.sequence = 42;
-    book.place_order(first);
-    book.place_order(second);
-    book.place_order(create_test_order(3, dec!(100.0), dec!(10.0), Side::Ask, OrderType::Limit));
+    book.place_order(first).unwrap();
+    book.place_order(second).unwrap();
+    book.place_order(create_test_order(3, dec!(100.0), dec!(10.0), Side::Ask, OrderType::Limit)).unwrap();
     let trades = book.get_trade_history(None);
     assert_eq!(trades[0].maker_order_id, 1);
     assert_eq!(trades[1].maker_order_id, 2);
 }
+
+#[test]
+fn test_ioc_cancels_remainder() {
+    let mut book = MatchingEngine::new();
+    book.place_order(create_test_order(1, dec!(100.0), dec!(4.0), Side::Ask, OrderType::Limit)).unwrap();
+
+    let mut ioc = create_test_order(2, dec!(100.0), dec!(10.0), Side::Bid, OrderType::Limit);
+    ioc.time_in_force = TimeInForce::Ioc;
+    let result = book.place_order(ioc).unwrap();
+
+    assert_eq!(result.status, OrderStatus::Cancelled);
+    assert_eq!(result.remaining_quantity, dec!(6.0));
+    assert_eq!(book.best_bid(), None);
+    assert_eq!(book.get_trade_history(None).len(), 1);
+}
+
+#[test]
+fn test_fok_kills_without_touching_book() {
+    let mut book = MatchingEngine::new();
+    book.place_order(create_test_order(1, dec!(100.0), dec!(4.0), Side::Ask, OrderType::Limit)).unwrap();
+    book.place_order(create_test_order(2, dec!(101.0), dec!(4.0), Side::Ask, OrderType::Limit)).unwrap();
+
+    let mut fok = create_test_order(3, dec!(101.0), dec!(10.0), Side::Bid, OrderType::Limit);
+    fok.time_in_force = TimeInForce::Fok;
+    let result = book.place_order(fok).unwrap();
+
+    assert_eq!((result.status, result.status_reason), (OrderStatus::Rejected, Some(StatusReason::FokUnfillable)));
+    assert_eq!(result.remaining_quantity, dec!(10.0));
+    assert!(book.get_trade_history(None).is_empty());
+    assert_eq!(book.get_order_book(10).1, vec![(dec!(100.0), dec!(4.0)), (dec!(101.0), dec!(4.0))]);
+
+    let mut fok = create_test_order(4, dec!(101.0), dec!(8.0), Side::Bid, OrderType::Limit);
+    fok.time_in_force = TimeInForce::Fok;
+    assert_eq!(book.place_order(fok).unwrap().status, OrderStatus::Filled);
+}
+
+#[test]
+fn test_gtd_and_day_orders_expire() {
+    let mut book = MatchingEngine::new();
+    let now = Utc::now();
+
+    let mut gtd = create_test_order(1, dec!(99.0), dec!(5.0), Side::Bid, OrderType::Limit);
+    gtd.time_in_force = TimeInForce::Gtd;
+    gtd.expire_time = Some(now + Duration::seconds(10));
+    book.place_order(gtd).unwrap();
+
+    let mut day = create_test_order(2, dec!(98.0), dec!(5.0), Side::Bid, OrderType::Limit);
+    day.time_in_force = TimeInForce::Day;
+    let day = book.place_order(day).unwrap();
+    assert!(day.expire_time.unwrap() > now);
+
+    assert!(book.expire_orders(now).is_empty());
+    assert_eq!(book.next_expiry(), Some(now + Duration::seconds(10)));
+
+    let expired = book.expire_orders(now + Duration::seconds(10));
+    assert_eq!(expired.len(), 1);
+    assert_eq!(expired[0].id, 1);
+    assert_eq!(book.next_expiry(), day.expire_time);
+    let gtd = book.get_order_status(1).unwrap();
+    assert_eq!((gtd.status, gtd.status_reason), (OrderStatus::Expired, Some(StatusReason::ExpireTime)));
+    assert_eq!(book.best_bid(), Some(dec!(98.0)));
+
+    let expired = book.expire_orders(day.expire_time.unwrap());
+    assert_eq!(expired.len(), 1);
+    assert_eq!(book.best_bid(), None);
+    assert_eq!(book.next_expiry(), None);
+}
+
+#[test]
+fn test_post_only_reject() {
+    let mut book = MatchingEngine::new();
+    book.place_order(create_test_order(1, dec!(100.0), dec!(5.0), Side::Ask, OrderType::Limit)).unwrap();
+
+    let mut crossing = create_test_order(2, dec!(100.0), dec!(5.0), Side::Bid, OrderType::Limit);
+    crossing.post_only = Some(PostOnly::Reject);
+    let rejected = book.place_order(crossing).unwrap();
+    assert_eq!((rejected.status, rejected.status_reason), (OrderStatus::Rejected, Some(StatusReason::PostOnlyWouldTake)));
+    assert!(book.get_trade_history(None).is_empty());
+    assert_eq!(book.best_bid(), None);
+
+    let mut passive = create_test_order(3, dec!(99.0), dec!(5.0), Side::Bid, OrderType::Limit);
+    passive.post_only = Some(PostOnly::Reject);
+    assert_eq!(book.place_order(passive).unwrap().status, OrderStatus::Pending);
+    assert_eq!(book.best_bid(), Some(dec!(99.0)));
+}
+
+#[test]
+fn test_post_only_reprice() {
+    let mut book = MatchingEngine::with_config(InstrumentConfig { tick_size: dec!(0.5), ..Default::default() }).unwrap();
+    book.place_order(create_test_order(1, dec!(100.0), dec!(5.0), Side::Bid, OrderType::Limit)).unwrap();
+
+    let mut crossing = create_test_order(2, dec!(98.0), dec!(5.0), Side::Ask, OrderType::Limit);
+    crossing.post_only = Some(PostOnly::Reprice);
+    let result = book.place_order(crossing).unwrap();
+
+    assert_eq!(result.status, OrderStatus::Pending);
+    assert_eq!(result.price, dec!(100.5));
+    assert!(book.get_trade_history(None).is_empty());
+    assert_eq!(book.best_ask(), Some(dec!(100.5)));
+}
+
+fn create_stop_order(id: u64, stop_price: rust_decimal::Decimal, price: rust_decimal::Decimal, quantity: rust_decimal::Decimal, side: Side, order_type: OrderType) -> Order {
+    let mut order = create_test_order(id, price, quantity, side, order_type);
+    order.stop_price = Some(stop_price);
+    order
+}
+
+#[test]
+fn test_stop_orders_cascade() {
+    let mut book = MatchingEngine::new();
+    book.place_order(create_test_order(1, dec!(99.0), dec!(5.0), Side::Bid, OrderType::Limit)).unwrap();
+    book.place_order(create_test_order(2, dec!(98.0), dec!(5.0), Side::Bid, OrderType::Limit)).unwrap();
+    book.place_order(create_test_order(3, dec!(97.0), dec!(5.0), Side::Bid, OrderType::Limit)).unwrap();
+
+    let parked = book.place_order(create_stop_order(10, dec!(99.0), dec!(0.0), dec!(5.0), Side::Ask, OrderType::Stop)).unwrap();
+    book.place_order(create_stop_order(11, dec!(98.0), dec!(0.0), dec!(5.0), Side::Ask, OrderType::Stop)).unwrap();
+    assert_eq!(parked.status, OrderStatus::Pending);
+    assert!(book.get_trade_history(None).is_empty());
+
+    // trading at 99 elects the 99 stop, whose fill at 98 elects the 98 stop
+    book.place_order(create_test_order(20, dec!(99.0), dec!(5.0), Side::Ask, OrderType::Limit)).unwrap();
+
+    let trades = book.get_trade_history(None);
+    assert_eq!(trades.len(), 3);
+    assert_eq!((trades[1].taker_order_id, trades[1].price), (10, dec!(98.0)));
+    assert_eq!((trades[2].taker_order_id, trades[2].price), (11, dec!(97.0)));
+    assert_eq!(book.get_order_status(10).unwrap().status, OrderStatus::Filled);
+    assert_eq!(book.get_order_status(11).unwrap().status, OrderStatus::Filled);
+    assert_eq!(book.best_bid(), None);
+}
+
+#[test]
+fn test_stop_limit_rests_after_trigger() {
+    let mut book = MatchingEngine::new();
+    book.place_order(create_test_order(1, dec!(101.0), dec!(2.0), Side::Ask, OrderType::Limit)).unwrap();
+    book.place_order(create_stop_order(2, dec!(101.0), dec!(101.0), dec!(5.0), Side::Bid, OrderType::StopLimit)).unwrap();
+    book.place_order(create_stop_order(3, dec!(105.0), dec!(105.0), dec!(5.0), Side::Bid, OrderType::StopLimit)).unwrap();
+
+    book.place_order(create_test_order(4, dec!(101.0), dec!(1.0), Side::Bid, OrderType::Limit)).unwrap();
+
+    let elected = book.get_order_status(2).unwrap();
+    assert_eq!(elected.status, OrderStatus::PartiallyFilled);
+    assert_eq!(elected.remaining_quantity, dec!(4.0));
+    assert_eq!(book.best_bid(), Some(dec!(101.0)));
+
+    // the untriggered stop can still be cancelled out of the trigger book
+    assert_eq!(book.cancel_order(3).unwrap().status, OrderStatus::Cancelled);
+    book.place_order(create_test_order(5, dec!(105.0), dec!(10.0), Side::Ask, OrderType::Limit)).unwrap();
+    book.place_order(create_test_order(6, dec!(105.0), dec!(1.0), Side::Bid, OrderType::Limit)).unwrap();
+    assert_eq!(book.get_order_status(3).unwrap().remaining_quantity, dec!(5.0));
+}
+
+#[test]
+fn test_placed_order_reflects_the_stops_it_elects() {
+    let mut book = MatchingEngine::new();
+    book.place_order(create_test_order(1, dec!(100.0), dec!(1.0), Side::Ask, OrderType::Limit)).unwrap();
+    book.place_order(create_stop_order(2, dec!(100.0), dec!(0.0), dec!(4.0), Side::Ask, OrderType::Stop)).unwrap();
+
+    // the bid's first fill elects the stop, which then fills the rest of the bid
+    let placed = book.place_order(create_test_order(3, dec!(100.0), dec!(5.0), Side::Bid, OrderType::Limit)).unwrap();
+    assert_eq!((placed.status, placed.remaining_quantity), (OrderStatus::Filled, dec!(0.0)));
+    assert_eq!(book.get_order_status(3), Some(placed));
+    assert_eq!(book.get_order_status(2).unwrap().status, OrderStatus::Filled);
+    assert_eq!(book.best_bid(), None);
+}
+
+#[test]
+fn test_iceberg_shows_slice_and_loses_priority_on_replenish() {
+    let mut book = MatchingEngine::new();
+
+    let mut iceberg = create_test_order(1, dec!(100.0), dec!(10.0), Side::Bid, OrderType::Limit);
+    iceberg.display_quantity = Some(dec!(3.0));
+    book.place_order(iceberg).unwrap();
+    book.place_order(create_test_order(2, dec!(100.0), dec!(2.0), Side::Bid, OrderType::Limit)).unwrap();
+    assert_eq!(book.get_order_book(1).0, vec![(dec!(100.0), dec!(5.0))]);
+
+    book.place_order(create_test_order(3, dec!(100.0), dec!(4.0), Side::Ask, OrderType::Limit)).unwrap();
+    let trades = book.get_trade_history(None);
+    assert_eq!((trades[0].maker_order_id, trades[0].quantity), (1, dec!(3.0)));
+    assert_eq!((trades[1].maker_order_id, trades[1].quantity), (2, dec!(1.0)));
+
+    // replenished slice now queues behind order 2
+    let level = book.orders_at_price(dec!(100.0), Side::Bid);
+    assert_eq!(level.iter().map(|o| o.id).collect::<Vec<_>>(), vec![2, 1]);
+    assert_eq!(book.get_order_book(1).0, vec![(dec!(100.0), dec!(4.0))]);
+
+    // a large taker keeps trading through successive slices
+    book.place_order(create_test_order(4, dec!(100.0), dec!(8.0), Side::Ask, OrderType::Limit)).unwrap();
+    assert_eq!(book.best_bid(), None);
+    assert_eq!(book.get_trade_history(None).len(), 6);
+}
+
+#[test]
+fn test_amend_reduce_keeps_priority() {
+    let mut book = MatchingEngine::new();
+    book.place_order(create_test_order(1, dec!(100.0), dec!(10.0), Side::Bid, OrderType::Limit)).unwrap();
+    book.place_order(create_test_order(2, dec!(100.0), dec!(10.0), Side::Bid, OrderType::Limit)).unwrap();
+
+    let amended = book.amend_order(1, None, Some(dec!(4.0)), 99).unwrap();
+    assert_eq!(amended.sequence, 1);
+    assert_eq!(amended.remaining_quantity, dec!(4.0));
+    assert_eq!(book.get_order_book(1).0, vec![(dec!(100.0), dec!(14.0))]);
+
+    book.place_order(create_test_order(3, dec!(100.0), dec!(5.0), Side::Ask, OrderType::Limit)).unwrap();
+    let trades = book.get_trade_history(None);
+    assert_eq!((trades[0].maker_order_id, trades[0].quantity), (1, dec!(4.0)));
+    assert_eq!((trades[1].maker_order_id, trades[1].quantity), (2, dec!(1.0)));
+
+    // can't amend below what has already filled
+    assert_eq!(book.amend_order(2, None, Some(dec!(1.0)), 100), Err(EngineError::InvalidQuantity(dec!(1.0))));
+}
+
+#[test]
+fn test_amend_increase_or_reprice_resequences() {
+    let mut book = MatchingEngine::new();
+    book.place_order(create_test_order(1, dec!(100.0), dec!(5.0), Side::Bid, OrderType::Limit)).unwrap();
+    book.place_order(create_test_order(2, dec!(100.0), dec!(5.0), Side::Bid, OrderType::Limit)).unwrap();
+    book.place_order(create_test_order(3, dec!(102.0), dec!(3.0), Side::Ask, OrderType::Limit)).unwrap();
+
+    let amended = book.amend_order(1, None, Some(dec!(6.0)), 10).unwrap();
+    assert_eq!(amended.sequence, 10);
+    let level = book.orders_at_price(dec!(100.0), Side::Bid);
+    assert_eq!(level.iter().map(|o| o.id).collect::<Vec<_>>(), vec![2, 1]);
+
+    // repricing through the offer trades immediately and rests the remainder
+    let amended = book.amend_order(2, Some(dec!(102.0)), None, 11).unwrap();
+    assert_eq!(amended.status, OrderStatus::PartiallyFilled);
+    assert_eq!(amended.remaining_quantity, dec!(2.0));
+    assert_eq!(book.get_trade_history(None)[0].taker_order_id, 2);
+    assert_eq!(book.best_bid(), Some(dec!(102.0)));
+}
+
+fn create_owned_order(id: u64, owner_id: u64, price: rust_decimal::Decimal, quantity: rust_decimal::Decimal, side: Side, stp_mode: Option<StpMode>) -> Order {
+    let mut order = create_test_order(id, price, quantity, side, OrderType::Limit);
+    order.owner_id = Some(owner_id);
+    order.stp_mode = stp_mode;
+    order
+}
+
+#[test]
+fn test_stp_cancel_newest_and_oldest() {
+    let mut book = MatchingEngine::new();
+    book.place_order(create_owned_order(1, 7, dec!(100.0), dec!(5.0), Side::Ask, None)).unwrap();
+    book.place_order(create_owned_order(2, 8, dec!(100.0), dec!(5.0), Side::Ask, None)).unwrap();
+
+    let newest = book.place_order(create_owned_order(3, 7, dec!(100.0), dec!(8.0), Side::Bid, Some(StpMode::CancelNewest))).unwrap();
+    assert_eq!(newest.status, OrderStatus::Cancelled);
+    assert_eq!(newest.stp_events.len(), 1);
+    assert_eq!(newest.stp_events[0].resting_order_id, 1);
+    assert_eq!(newest.stp_events[0].taker_quantity_cancelled, dec!(8.0));
+    assert!(book.get_trade_history(None).is_empty());
+    assert_eq!(book.best_bid(), None);
+
+    let oldest = book.place_order(create_owned_order(4, 7, dec!(100.0), dec!(8.0), Side::Bid, Some(StpMode::CancelOldest))).unwrap();
+    assert_eq!(book.get_order_status(1).unwrap().status, OrderStatus::Cancelled);
+    assert_eq!(oldest.stp_events[0].resting_quantity_cancelled, dec!(5.0));
+    assert_eq!(oldest.remaining_quantity, dec!(3.0));
+    let trades = book.get_trade_history(None);
+    assert_eq!(trades.len(), 1);
+    assert_eq!(trades[0].maker_order_id, 2);
+}
+
+#[test]
+fn test_stp_cancel_both_and_decrement() {
+    let mut book = MatchingEngine::new();
+    book.place_order(create_owned_order(1, 7, dec!(100.0), dec!(5.0), Side::Ask, None)).unwrap();
+
+    let both = book.place_order(create_owned_order(2, 7, dec!(100.0), dec!(2.0), Side::Bid, Some(StpMode::CancelBoth))).unwrap();
+    assert_eq!(both.status, OrderStatus::Cancelled);
+    assert_eq!(book.get_order_status(1).unwrap().status, OrderStatus::Cancelled);
+    assert_eq!(book.best_ask(), None);
+
+    book.place_order(create_owned_order(3, 7, dec!(100.0), dec!(5.0), Side::Ask, None)).unwrap();
+    let decremented = book.place_order(create_owned_order(4, 7, dec!(100.0), dec!(2.0), Side::Bid, Some(StpMode::DecrementAndCancel))).unwrap();
+    assert_eq!(decremented.status, OrderStatus::Cancelled);
+    assert_eq!(decremented.stp_events[0].taker_quantity_cancelled, dec!(2.0));
+    assert_eq!(decremented.stp_events[0].resting_quantity_cancelled, dec!(2.0));
+    assert_eq!(book.get_order_book(1).1, vec![(dec!(100.0), dec!(3.0))]);
+    assert!(book.get_trade_history(None).is_empty());
+}
+
+#[test]
+fn test_fok_accounts_for_self_trade_prevention() {
+    let mut book = MatchingEngine::new();
+    book.place_order(create_owned_order(1, 2, dec!(100.0), dec!(5.0), Side::Ask, None)).unwrap();
+    book.place_order(create_owned_order(2, 1, dec!(100.0), dec!(5.0), Side::Ask, None)).unwrap();
+    book.place_order(create_owned_order(3, 3, dec!(101.0), dec!(5.0), Side::Ask, None)).unwrap();
+
+    // cancelled at its own order, so it could never get the second five
+    let mut fok = create_owned_order(4, 1, dec!(101.0), dec!(10.0), Side::Bid, Some(StpMode::CancelNewest));
+    fok.time_in_force = TimeInForce::Fok;
+    let killed = book.place_order(fok).unwrap();
+    assert_eq!((killed.status, killed.status_reason), (OrderStatus::Rejected, Some(StatusReason::FokUnfillable)));
+    assert!(book.get_trade_history(None).is_empty());
+
+    // decrementing takes five off, so sixteen still needs eleven where only ten can fill
+    let mut fok = create_owned_order(5, 1, dec!(101.0), dec!(16.0), Side::Bid, Some(StpMode::DecrementAndCancel));
+    fok.time_in_force = TimeInForce::Fok;
+    let killed = book.place_order(fok).unwrap();
+    assert_eq!(killed.status, OrderStatus::Rejected);
+    let mut fok = create_owned_order(6, 1, dec!(101.0), dec!(12.0), Side::Bid, Some(StpMode::DecrementAndCancel));
+    fok.time_in_force = TimeInForce::Fok;
+    let filled = book.place_order(fok).unwrap();
+    assert_eq!((filled.status, filled.quantity), (OrderStatus::Filled, dec!(7.0)));
+    assert_eq!(book.get_order_status(2).unwrap().status, OrderStatus::Cancelled);
+}
+
+#[test]
+fn test_stp_cancelling_bracket_parent_drops_children() {
+    let mut book = MatchingEngine::new();
+    let parent = create_owned_order(1, 7, dec!(100.0), dec!(2.0), Side::Bid, None);
+    let take_profit = create_test_order(2, dec!(110.0), dec!(2.0), Side::Ask, OrderType::Limit);
+    let stop_loss = create_stop_order(3, dec!(90.0), dec!(0.0), dec!(2.0), Side::Ask, OrderType::Stop);
+    book.place_bracket(9, parent, take_profit, stop_loss).unwrap();
+
+    book.place_order(create_owned_order(4, 7, dec!(100.0), dec!(2.0), Side::Ask, Some(StpMode::CancelOldest))).unwrap();
+    assert_eq!(book.get_order_status(1).unwrap().status, OrderStatus::Cancelled);
+    assert_eq!(book.get_order_status(2).unwrap().status, OrderStatus::Cancelled);
+    assert_eq!(book.get_order_status(3).unwrap().status, OrderStatus::Cancelled);
+    assert!(book.place_oco(9, vec![]).is_ok());
+}
+
+#[test]
+fn test_pro_rata_allocation_with_minimum() {
+    let config = InstrumentConfig {
+        matching_policy: Arc::new(ProRata { min_allocation: dec!(2), lot_size: dec!(1) }),
+        ..Default::default()
+    };
+    let mut book = MatchingEngine::with_config(config).unwrap();
+    book.place_order(create_test_order(1, dec!(100.0), dec!(10.0), Side::Bid, OrderType::Limit)).unwrap();
+    book.place_order(create_test_order(2, dec!(100.0), dec!(30.0), Side::Bid, OrderType::Limit)).unwrap();
+    book.place_order(create_test_order(3, dec!(100.0), dec!(60.0), Side::Bid, OrderType::Limit)).unwrap();
+
+    // 15 split 10/30/60 -> 1.5/4.5/9 -> 1 (under the minimum, dropped)/4/9, leftover 2 FIFO
+    book.place_order(create_test_order(4, dec!(100.0), dec!(15.0), Side::Ask, OrderType::Limit)).unwrap();
+    let fills: Vec<_> = book.get_trade_history(None).iter().map(|t| (t.maker_order_id, t.quantity)).collect();
+    assert_eq!(fills, vec![(1, dec!(2)), (2, dec!(4)), (3, dec!(9))]);
+}
+
+#[test]
+fn test_top_order_then_pro_rata() {
+    let config = InstrumentConfig {
+        matching_policy: Arc::new(TopOrderProRata {
+            pro_rata: ProRata { min_allocation: dec!(1), lot_size: dec!(1) },
+        }),
+        ..Default::default()
+    };
+    let mut book = MatchingEngine::with_config(config).unwrap();
+    book.place_order(create_test_order(1, dec!(100.0), dec!(5.0), Side::Ask, OrderType::Limit)).unwrap();
+    book.place_order(create_test_order(2, dec!(100.0), dec!(10.0), Side::Ask, OrderType::Limit)).unwrap();
+    book.place_order(create_test_order(3, dec!(100.0), dec!(30.0), Side::Ask, OrderType::Limit)).unwrap();
+
+    book.place_order(create_test_order(4, dec!(100.0), dec!(13.0), Side::Bid, OrderType::Limit)).unwrap();
+    let fills: Vec<_> = book.get_trade_history(None).iter().map(|t| (t.maker_order_id, t.quantity)).collect();
+    assert_eq!(fills, vec![(1, dec!(5)), (2, dec!(2)), (3, dec!(6))]);
+    assert_eq!(book.get_order_book(1).1, vec![(dec!(100.0), dec!(32.0))]);
+}
+
+#[test]
+fn test_call_auction_uncross() {
+    let mut book = MatchingEngine::new();
+    book.start_auction();
+    book.place_order(create_test_order(1, dec!(101.0), dec!(10.0), Side::Bid, OrderType::Limit)).unwrap();
+    book.place_order(create_test_order(2, dec!(100.0), dec!(5.0), Side::Bid, OrderType::Limit)).unwrap();
+    book.place_order(create_test_order(3, dec!(99.0), dec!(8.0), Side::Ask, OrderType::Limit)).unwrap();
+    book.place_order(create_test_order(4, dec!(100.0), dec!(6.0), Side::Ask, OrderType::Limit)).unwrap();
+
+    // crossed, but nothing trades during the auction
+    assert!(book.get_trade_history(None).is_empty());
+    let indicative = book.indicative_uncross().unwrap();
+    assert_eq!((indicative.price, indicative.volume, indicative.imbalance), (dec!(100.0), dec!(14.0), dec!(1.0)));
+
+    let uncross = book.uncross().unwrap();
+    assert_eq!(uncross, indicative);
+    assert_eq!(book.trading_phase(), TradingPhase::Continuous);
+
+    let trades = book.get_trade_history(None);
+    assert_eq!(trades.len(), 3);
+    assert!(trades.iter().all(|t| t.price == dec!(100.0) && t.timestamp == trades[0].timestamp));
+    assert_eq!(trades.iter().map(|t| t.quantity).sum::<rust_decimal::Decimal>(), dec!(14.0));
+    assert_eq!(book.get_order_status(2).unwrap().remaining_quantity, dec!(1.0));
+    assert_eq!(book.get_order_book(10), (vec![(dec!(100.0), dec!(1.0))], vec![]));
+}
+
+#[test]
+fn test_auction_leaves_out_all_or_none_orders_it_would_part_fill() {
+    let mut book = MatchingEngine::new();
+    book.start_auction();
+    let mut aon = create_test_order(1, dec!(101.0), dec!(10.0), Side::Bid, OrderType::Limit);
+    aon.all_or_none = true;
+    book.place_order(aon).unwrap();
+    book.place_order(create_test_order(2, dec!(100.0), dec!(3.0), Side::Ask, OrderType::Limit)).unwrap();
+    assert_eq!(book.indicative_uncross(), None);
+
+    // without the all-or-none bid the uncross moves down to the bid behind it
+    book.place_order(create_test_order(3, dec!(100.0), dec!(2.0), Side::Bid, OrderType::Limit)).unwrap();
+    let uncross = book.uncross().unwrap();
+    assert_eq!((uncross.price, uncross.volume, uncross.imbalance), (dec!(100.0), dec!(2.0), dec!(1.0)));
+    let aon = book.get_order_status(1).unwrap();
+    assert_eq!((aon.status, aon.remaining_quantity), (OrderStatus::Pending, dec!(10.0)));
+    assert_eq!(book.get_order_status(3).unwrap().status, OrderStatus::Filled);
+    // the all-or-none bid rests through the offer, but isn't shown, so the book doesn't read as crossed
+    assert_eq!(book.get_order_book(10), (vec![], vec![(dec!(100.0), dec!(1.0))]));
+}
+
+#[test]
+fn test_auction_tie_breaks_on_reference_price() {
+    let mut book = MatchingEngine::new();
+    book.place_order(create_test_order(1, dec!(103.0), dec!(1.0), Side::Bid, OrderType::Limit)).unwrap();
+    book.place_order(create_test_order(2, dec!(103.0), dec!(1.0), Side::Ask, OrderType::Limit)).unwrap();
+
+    book.start_auction();
+    book.place_order(create_test_order(3, dec!(102.0), dec!(5.0), Side::Bid, OrderType::Limit)).unwrap();
+    book.place_order(create_test_order(4, dec!(101.0), dec!(5.0), Side::Ask, OrderType::Limit)).unwrap();
+    // market and IOC orders can't wait for the uncross
+    assert_eq!(book.place_order(create_test_order(5, dec!(0.0), dec!(5.0), Side::Bid, OrderType::Market)).unwrap().status, OrderStatus::Rejected);
+
+    // 101 and 102 both clear 5 with no imbalance; 102 is closer to the last trade at 103
+    assert_eq!(book.uncross().unwrap().price, dec!(102.0));
+}
+
+#[test]
+fn test_frequent_batch_auction_clears_at_uniform_price() {
+    let config = InstrumentConfig {
+        batch_interval: Some(std::time::Duration::from_millis(100)),
+        ..InstrumentConfig::default()
+    };
+    let mut book = MatchingEngine::with_config(config).unwrap();
+    assert_eq!(book.trading_phase(), TradingPhase::Batch);
+
+    book.place_order(create_test_order(1, dec!(102.0), dec!(4.0), Side::Bid, OrderType::Limit)).unwrap();
+    book.place_order(create_test_order(2, dec!(100.0), dec!(3.0), Side::Ask, OrderType::Limit)).unwrap();
+    book.place_order(create_test_order(3, dec!(101.0), dec!(3.0), Side::Ask, OrderType::Limit)).unwrap();
+    assert!(book.get_trade_history(None).is_empty());
+
+    let clear = book.clear_batch().unwrap();
+    assert_eq!((clear.price, clear.volume), (dec!(101.0), dec!(4.0)));
+    let trades = book.get_trade_history(None);
+    assert!(trades.iter().all(|t| t.price == dec!(101.0)));
+
+    // still batching: the next crossing order waits for the next clear
+    assert_eq!(book.trading_phase(), TradingPhase::Batch);
+    book.place_order(create_test_order(4, dec!(101.0), dec!(2.0), Side::Bid, OrderType::Limit)).unwrap();
+    assert_eq!(book.get_trade_history(None).len(), trades.len());
+    assert_eq!(book.clear_batch().unwrap().volume, dec!(2.0));
+    assert!(book.clear_batch().is_none());
+}
+
+#[test]
+fn test_clear_batch_is_noop_for_continuous_instruments() {
+    let mut book = MatchingEngine::new();
+    book.start_auction();
+    book.place_order(create_test_order(1, dec!(100.0), dec!(1.0), Side::Bid, OrderType::Limit)).unwrap();
+    book.place_order(create_test_order(2, dec!(100.0), dec!(1.0), Side::Ask, OrderType::Limit)).unwrap();
+    assert!(book.clear_batch().is_none());
+    assert!(book.get_trade_history(None).is_empty());
+    assert!(book.uncross().is_some());
+    assert_eq!(book.trading_phase(), TradingPhase::Continuous);
+}
+
+fn create_pegged_order(id: u64, peg_type: PegType, offset: rust_decimal::Decimal, quantity: rust_decimal::Decimal, side: Side) -> Order {
+    let mut order = create_test_order(id, dec!(0.0), quantity, side, OrderType::Limit);
+    order.peg = Some(Peg { peg_type, offset, limit_price: None });
+    order
+}
+
+#[test]
+fn test_primary_peg_follows_bbo_and_loses_priority() {
+    let mut book = MatchingEngine::new();
+    book.place_order(create_test_order(1, dec!(100.0), dec!(5.0), Side::Bid, OrderType::Limit)).unwrap();
+    book.place_order(create_test_order(2, dec!(105.0), dec!(5.0), Side::Ask, OrderType::Limit)).unwrap();
+    let peg = book.place_order(create_pegged_order(3, PegType::Primary, dec!(0.0), dec!(2.0), Side::Bid)).unwrap();
+    assert_eq!(peg.price, dec!(100.0));
+
+    // a better bid moves the peg up, behind the order that set the new best
+    book.place_order(create_test_order(4, dec!(101.0), dec!(1.0), Side::Bid, OrderType::Limit)).unwrap();
+    let level: Vec<u64> = book.orders_at_price(dec!(101.0), Side::Bid).iter().map(|o| o.id).collect();
+    assert_eq!(level, vec![4, 3]);
+    assert!(book.orders_at_price(dec!(100.0), Side::Bid).iter().all(|o| o.id != 3));
+
+    // and back down when that bid goes away
+    book.cancel_order(4).unwrap();
+    assert_eq!(book.get_order_status(3).unwrap().price, dec!(100.0));
+    let level: Vec<u64> = book.orders_at_price(dec!(100.0), Side::Bid).iter().map(|o| o.id).collect();
+    assert_eq!(level, vec![1, 3]);
+}
+
+#[test]
+fn test_repriced_peg_trades_when_it_crosses() {
+    let mut book = MatchingEngine::new();
+    book.place_order(create_test_order(1, dec!(100.0), dec!(5.0), Side::Bid, OrderType::Limit)).unwrap();
+    book.place_order(create_test_order(2, dec!(103.0), dec!(5.0), Side::Ask, OrderType::Limit)).unwrap();
+    // two ticks through the best bid, capped at the best ask
+    let mut peg = create_pegged_order(3, PegType::Primary, dec!(-2.0), dec!(2.0), Side::Bid);
+    peg.peg = peg.peg.map(|p| Peg { limit_price: Some(dec!(103.0)), ..p });
+    assert_eq!(book.place_order(peg).unwrap().price, dec!(102.0));
+    assert!(book.get_trade_history(None).is_empty());
+
+    // a new best bid drags the peg onto the offer, where it takes
+    book.place_order(create_test_order(4, dec!(101.0), dec!(1.0), Side::Bid, OrderType::Limit)).unwrap();
+    let trades = book.get_trade_history(None);
+    assert_eq!(trades.len(), 1);
+    assert_eq!((trades[0].maker_order_id, trades[0].taker_order_id, trades[0].price), (2, 3, dec!(103.0)));
+    assert_eq!(book.get_order_status(3).unwrap().status, OrderStatus::Filled);
+
+    // midpoint of 101 x 103
+    let mid = book.place_order(create_pegged_order(5, PegType::Midpoint, dec!(0.0), dec!(1.0), Side::Ask)).unwrap();
+    assert_eq!(mid.price, dec!(102.0));
+}
+
+#[test]
+fn test_pegs_keep_to_their_reference() {
+    let mut book = MatchingEngine::new();
+    book.place_order(create_test_order(1, dec!(100.0), dec!(5.0), Side::Bid, OrderType::Limit)).unwrap();
+    book.place_order(create_test_order(2, dec!(103.0), dec!(5.0), Side::Ask, OrderType::Limit)).unwrap();
+    book.place_order(create_pegged_order(3, PegType::Primary, dec!(0.0), dec!(2.0), Side::Bid)).unwrap();
+
+    // the price is the peg's to set, so only the quantity can be amended
+    assert_eq!(book.amend_order(3, Some(dec!(101.0)), None, 10), Err(EngineError::NotAmendable(3)));
+    let amended = book.amend_order(3, None, Some(dec!(1.0)), 11).unwrap();
+    assert_eq!((amended.price, amended.quantity, amended.sequence), (dec!(100.0), dec!(1.0), 3));
+
+    // on the far touch with nothing to stop it, a market peg would chase the offer up the book
+    for offset in [dec!(0.0), dec!(-1.0)] {
+        let peg = create_pegged_order(4, PegType::Market, offset, dec!(1.0), Side::Bid);
+        assert!(matches!(book.place_order(peg), Err(EngineError::UnsupportedOrder(_))));
+    }
+    let passive = book.place_order(create_pegged_order(4, PegType::Market, dec!(1.0), dec!(1.0), Side::Bid)).unwrap();
+    assert_eq!(passive.price, dec!(102.0));
+    let mut capped = create_pegged_order(5, PegType::Market, dec!(0.0), dec!(1.0), Side::Bid);
+    capped.peg = capped.peg.map(|p| Peg { limit_price: Some(dec!(103.0)), ..p });
+    assert_eq!(book.place_order(capped).unwrap().status, OrderStatus::Filled);
+}
+
+fn trade_at(book: &mut MatchingEngine, id: u64, price: rust_decimal::Decimal) {
+    book.place_order(create_test_order(id, price, dec!(1.0), Side::Ask, OrderType::Limit)).unwrap();
+    book.place_order(create_test_order(id + 1, price, dec!(1.0), Side::Bid, OrderType::Limit)).unwrap();
+}
+
+#[test]
+fn test_trailing_stop_ratchets_then_triggers() {
+    let mut book = MatchingEngine::new();
+    book.place_order(create_test_order(1, dec!(90.0), dec!(10.0), Side::Bid, OrderType::Limit)).unwrap();
+    trade_at(&mut book, 10, dec!(100.0));
+
+    let mut stop = create_stop_order(2, dec!(95.0), dec!(0.0), dec!(2.0), Side::Ask, OrderType::Stop);
+    stop.trail = Some(Trail::Amount(dec!(5.0)));
+    book.place_order(stop).unwrap();
+
+    // follows the price up...
+    trade_at(&mut book, 20, dec!(104.0));
+    assert_eq!(book.get_order_status(2).unwrap().stop_price, Some(dec!(99.0)));
+    // ...but never back down
+    trade_at(&mut book, 30, dec!(102.0));
+    assert_eq!(book.get_order_status(2).unwrap().stop_price, Some(dec!(99.0)));
+
+    trade_at(&mut book, 40, dec!(99.0));
+    let last = book.get_trade_history(Some(1));
+    assert_eq!((last[0].maker_order_id, last[0].taker_order_id, last[0].price), (1, 2, dec!(90.0)));
+    assert_eq!(book.get_order_status(2).unwrap().status, OrderStatus::Filled);
+}
+
+#[test]
+fn test_trailing_stop_limit_moves_its_limit_by_percent() {
+    let mut book = MatchingEngine::new();
+    trade_at(&mut book, 10, dec!(100.0));
+
+    let mut stop = create_stop_order(1, dec!(120.0), dec!(122.0), dec!(1.0), Side::Bid, OrderType::StopLimit);
+    stop.trail = Some(Trail::Percent(dec!(10.0)));
+    book.place_order(stop).unwrap();
+    let parked = book.get_order_status(1).unwrap();
+    assert_eq!((parked.stop_price, parked.price), (Some(dec!(110.0)), dec!(112.0)));
+
+    trade_at(&mut book, 20, dec!(90.0));
+    let parked = book.get_order_status(1).unwrap();
+    assert_eq!((parked.stop_price, parked.price), (Some(dec!(99.0)), dec!(101.0)));
+}
+
+#[test]
+fn test_market_remainder_is_not_left_open() {
+    let mut book = MatchingEngine::new();
+    book.place_order(create_test_order(1, dec!(100.0), dec!(1.0), Side::Ask, OrderType::Limit)).unwrap();
+    let market = book.place_order(create_test_order(2, dec!(0.0), dec!(3.0), Side::Bid, OrderType::Market)).unwrap();
+    assert_eq!((market.status, market.remaining_quantity), (OrderStatus::Cancelled, dec!(2.0)));
+    assert_eq!(book.get_order_status(2).unwrap().status, OrderStatus::Cancelled);
+    assert_eq!(book.best_bid(), None);
+}
+
+#[test]
+fn test_market_to_limit_rests_at_last_fill() {
+    let mut book = MatchingEngine::new();
+    book.place_order(create_test_order(1, dec!(100.0), dec!(1.0), Side::Ask, OrderType::Limit)).unwrap();
+    book.place_order(create_test_order(2, dec!(101.0), dec!(1.0), Side::Ask, OrderType::Limit)).unwrap();
+    let mtl = book.place_order(create_test_order(3, dec!(0.0), dec!(5.0), Side::Bid, OrderType::MarketToLimit)).unwrap();
+    assert_eq!((mtl.order_type, mtl.price, mtl.status), (OrderType::Limit, dec!(101.0), OrderStatus::PartiallyFilled));
+    assert_eq!(book.get_order_book(10).0, vec![(dec!(101.0), dec!(3.0))]);
+
+    // nothing to fill against: there is no price to rest at
+    let mtl = book.place_order(create_test_order(4, dec!(0.0), dec!(1.0), Side::Ask, OrderType::MarketToLimit)).unwrap();
+    assert_eq!(mtl.status, OrderStatus::Filled);
+    let mtl = book.place_order(create_test_order(5, dec!(0.0), dec!(1.0), Side::Bid, OrderType::MarketToLimit)).unwrap();
+    assert_eq!(mtl.status, OrderStatus::Cancelled);
+}
+
+#[test]
+fn test_protection_price_and_slippage_band_stop_the_sweep() {
+    let config = InstrumentConfig { max_slippage: Some(dec!(1.0)), ..InstrumentConfig::default() };
+    let mut book = MatchingEngine::with_config(config).unwrap();
+    for (id, price) in [(1, dec!(100.0)), (2, dec!(101.0)), (3, dec!(102.0)), (4, dec!(103.0))] {
+        book.place_order(create_test_order(id, price, dec!(1.0), Side::Ask, OrderType::Limit)).unwrap();
+    }
+
+    let mut protected = create_test_order(5, dec!(0.0), dec!(4.0), Side::Bid, OrderType::Market);
+    protected.protection_price = Some(dec!(100.5));
+    let protected = book.place_order(protected).unwrap();
+    assert_eq!((protected.remaining_quantity, protected.status), (dec!(3.0), OrderStatus::Cancelled));
+
+    // 1% over the 101 touch lets it take 101 and 102 but not 103
+    let swept = book.place_order(create_test_order(6, dec!(0.0), dec!(4.0), Side::Bid, OrderType::Market)).unwrap();
+    assert_eq!(swept.protection_price, Some(dec!(102.01)));
+    assert_eq!((swept.remaining_quantity, swept.status), (dec!(2.0), OrderStatus::Cancelled));
+    assert_eq!(book.best_ask(), Some(dec!(103.0)));
+}
+
+#[test]
+fn test_min_quantity_taker() {
+    let mut book = MatchingEngine::new();
+    book.place_order(create_test_order(1, dec!(100.0), dec!(2.0), Side::Ask, OrderType::Limit)).unwrap();
+
+    let mut taker = create_test_order(2, dec!(100.0), dec!(5.0), Side::Bid, OrderType::Limit);
+    taker.min_quantity = Some(dec!(3.0));
+    assert_eq!(book.place_order(taker).unwrap().status, OrderStatus::Rejected);
+    assert!(book.get_trade_history(None).is_empty());
+
+    let mut taker = create_test_order(3, dec!(100.0), dec!(5.0), Side::Bid, OrderType::Limit);
+    taker.min_quantity = Some(dec!(2.0));
+    let taker = book.place_order(taker).unwrap();
+    assert_eq!((taker.status, taker.remaining_quantity), (OrderStatus::PartiallyFilled, dec!(3.0)));
+
+    // nothing to trade against falls short of the minimum just the same
+    let mut passive = create_test_order(4, dec!(99.0), dec!(5.0), Side::Bid, OrderType::Limit);
+    passive.min_quantity = Some(dec!(1.0));
+    let passive = book.place_order(passive).unwrap();
+    assert_eq!((passive.status, passive.status_reason), (OrderStatus::Rejected, Some(StatusReason::MinQuantityUnfillable)));
+    assert_eq!(book.get_order_book(10).0, vec![(dec!(100.0), dec!(3.0))]);
+}
+
+#[test]
+fn test_all_or_none_interest_is_left_out_of_the_bbo() {
+    let mut book = MatchingEngine::new();
+    let mut aon = create_test_order(1, dec!(102.0), dec!(10.0), Side::Ask, OrderType::Limit);
+    aon.all_or_none = true;
+    book.place_order(aon).unwrap();
+    book.place_order(create_test_order(2, dec!(100.0), dec!(1.0), Side::Ask, OrderType::Limit)).unwrap();
+    book.place_order(create_test_order(3, dec!(105.0), dec!(3.0), Side::Bid, OrderType::Limit)).unwrap();
+
+    // the bid is too small for the AON and rests through it at 105
+    assert_eq!(book.get_order_status(1).unwrap().remaining_quantity, dec!(10.0));
+    assert_eq!((book.best_bid(), book.best_ask()), (Some(dec!(105.0)), None));
+    assert_eq!(book.get_order_book(10), (vec![(dec!(105.0), dec!(2.0))], vec![]));
+
+    // with one side of the lit BBO empty there's no mid to cross dark orders at, nor a peg reference
+    let mut dark_bid = create_test_order(4, dec!(110.0), dec!(1.0), Side::Bid, OrderType::Limit);
+    dark_bid.midpoint = true;
+    book.place_order(dark_bid).unwrap();
+    let mut dark_ask = create_test_order(5, dec!(100.0), dec!(1.0), Side::Ask, OrderType::Limit);
+    dark_ask.midpoint = true;
+    book.place_order(dark_ask).unwrap();
+    assert_eq!(book.get_trade_history(None).len(), 1);
+    let mut peg = create_test_order(6, dec!(0.0), dec!(1.0), Side::Ask, OrderType::Limit);
+    peg.peg = Some(Peg { peg_type: PegType::Primary, offset: dec!(0.0), limit_price: None });
+    assert_eq!(book.place_order(peg).unwrap().status_reason, Some(StatusReason::NoPegReference));
+}
+
+#[test]
+fn test_all_or_none_is_skipped_without_blocking_the_queue() {
+    let mut book = MatchingEngine::new();
+    let mut aon = create_test_order(1, dec!(100.0), dec!(5.0), Side::Ask, OrderType::Limit);
+    aon.all_or_none = true;
+    book.place_order(aon).unwrap();
+    book.place_order(create_test_order(2, dec!(100.0), dec!(2.0), Side::Ask, OrderType::Limit)).unwrap();
+    book.place_order(create_test_order(3, dec!(101.0), dec!(2.0), Side::Ask, OrderType::Limit)).unwrap();
+
+    // too small for the AON: trades behind it at the same level, then at the next one
+    book.place_order(create_test_order(4, dec!(101.0), dec!(3.0), Side::Bid, OrderType::Limit)).unwrap();
+    let makers: Vec<u64> = book.get_trade_history(None).iter().map(|t| t.maker_order_id).collect();
+    assert_eq!(makers, vec![2, 3]);
+    assert_eq!(book.orders_at_price(dec!(100.0), Side::Ask)[0].remaining_quantity, dec!(5.0));
+
+    // big enough: the AON fills in full
+    book.place_order(create_test_order(5, dec!(100.0), dec!(5.0), Side::Bid, OrderType::Limit)).unwrap();
+    let last = book.get_trade_history(Some(1));
+    assert_eq!((last[0].maker_order_id, last[0].quantity), (1, dec!(5.0)));
+    assert_eq!(book.best_ask(), Some(dec!(101.0)));
+}
+
+#[test]
+fn test_oco_fill_cancels_sibling_and_keeps_membership() {
+    let mut book = MatchingEngine::new();
+    let take_profit = create_test_order(1, dec!(105.0), dec!(3.0), Side::Ask, OrderType::Limit);
+    let stop_loss = create_stop_order(2, dec!(95.0), dec!(0.0), dec!(3.0), Side::Ask, OrderType::Stop);
+    let placed = book.place_oco(7, vec![take_profit, stop_loss]).unwrap();
+    assert!(placed.iter().all(|o| o.group.map(|g| (g.group_id, g.role)) == Some((7, GroupRole::Leg))));
+    assert_eq!(book.place_oco(7, vec![]), Err(EngineError::DuplicateGroupId(7)));
+
+    book.place_order(create_test_order(3, dec!(105.0), dec!(1.0), Side::Bid, OrderType::Limit)).unwrap();
+    let leg = book.get_order_status(1).unwrap();
+    assert_eq!((leg.status, leg.remaining_quantity), (OrderStatus::PartiallyFilled, dec!(2.0)));
+    assert_eq!(leg.group.unwrap().group_id, 7);
+    assert_eq!(book.get_order_status(2).unwrap().status, OrderStatus::Cancelled);
+}
+
+#[test]
+fn test_groups_retire_once_every_member_is_done() {
+    let mut book = MatchingEngine::new();
+    // neither leg has anything to trade against
+    let legs = || {
+        [1, 2].map(|id| {
+            let mut leg = create_test_order(id, dec!(100.0), dec!(1.0), Side::Bid, OrderType::Limit);
+            leg.time_in_force = TimeInForce::Ioc;
+            leg
+        })
+    };
+    let placed = book.place_oco(7, legs().to_vec()).unwrap();
+    assert!(placed.iter().all(|o| o.status_reason == Some(StatusReason::ImmediateOrCancel)));
+    let legs = legs().map(|mut leg| {
+        leg.id += 10;
+        leg
+    });
+    assert!(book.place_oco(7, legs.to_vec()).is_ok());
+
+    // a bracket whose children were both cancelled while parked
+    let parent = create_test_order(3, dec!(100.0), dec!(1.0), Side::Bid, OrderType::Limit);
+    let take_profit = create_test_order(4, dec!(110.0), dec!(1.0), Side::Ask, OrderType::Limit);
+    let stop_loss = create_stop_order(5, dec!(90.0), dec!(0.0), dec!(1.0), Side::Ask, OrderType::Stop);
+    book.place_bracket(9, parent, take_profit, stop_loss).unwrap();
+    book.cancel_order(4).unwrap();
+    book.cancel_order(5).unwrap();
+    book.place_order(create_test_order(6, dec!(100.0), dec!(1.0), Side::Ask, OrderType::Limit)).unwrap();
+    assert_eq!(book.get_order_status(3).unwrap().status, OrderStatus::Filled);
+    assert!(book.place_oco(9, vec![]).is_ok());
+}
+
+#[test]
+fn test_parked_bracket_children_expire() {
+    let mut book = MatchingEngine::new();
+    let now = Utc::now();
+    let parent = create_test_order(1, dec!(100.0), dec!(1.0), Side::Bid, OrderType::Limit);
+    let mut take_profit = create_test_order(2, dec!(110.0), dec!(1.0), Side::Ask, OrderType::Limit);
+    take_profit.time_in_force = TimeInForce::Gtd;
+    take_profit.expire_time = Some(now + Duration::minutes(1));
+    // already due, with no sweep between now and the parent filling
+    let mut stop_loss = create_stop_order(3, dec!(90.0), dec!(0.0), dec!(1.0), Side::Ask, OrderType::Stop);
+    stop_loss.time_in_force = TimeInForce::Gtd;
+    stop_loss.expire_time = Some(now - Duration::minutes(1));
+    book.place_bracket(9, parent, take_profit, stop_loss).unwrap();
+
+    let expired = book.expire_orders(now + Duration::minutes(2));
+    assert_eq!(expired.iter().map(|o| o.id).collect::<Vec<_>>(), vec![3, 2]);
+    assert_eq!(book.get_order_status(1).unwrap().status, OrderStatus::Pending);
+
+    let parent = create_test_order(4, dec!(100.0), dec!(1.0), Side::Bid, OrderType::Limit);
+    let take_profit = create_test_order(5, dec!(110.0), dec!(1.0), Side::Ask, OrderType::Limit);
+    let mut stop_loss = create_stop_order(6, dec!(90.0), dec!(0.0), dec!(1.0), Side::Ask, OrderType::Stop);
+    stop_loss.time_in_force = TimeInForce::Gtd;
+    stop_loss.expire_time = Some(now - Duration::minutes(1));
+    book.place_bracket(8, parent, take_profit, stop_loss).unwrap();
+    book.place_order(create_test_order(7, dec!(100.0), dec!(2.0), Side::Ask, OrderType::Limit)).unwrap();
+    let stop_loss = book.get_order_status(6).unwrap();
+    assert_eq!((stop_loss.status, stop_loss.status_reason), (OrderStatus::Expired, Some(StatusReason::ExpireTime)));
+    assert_eq!(book.get_order_status(5).unwrap().status, OrderStatus::Pending);
+}
+
+#[test]
+fn test_bracket_children_activate_after_parent_fills() {
+    let mut book = MatchingEngine::new();
+    let parent = create_test_order(1, dec!(100.0), dec!(2.0), Side::Bid, OrderType::Limit);
+    let take_profit = create_test_order(2, dec!(110.0), dec!(2.0), Side::Ask, OrderType::Limit);
+    let stop_loss = create_stop_order(3, dec!(90.0), dec!(0.0), dec!(2.0), Side::Ask, OrderType::Stop);
+    book.place_bracket(9, parent, take_profit, stop_loss).unwrap();
+
+    // a partial fill isn't enough
+    book.place_order(create_test_order(4, dec!(100.0), dec!(1.0), Side::Ask, OrderType::Limit)).unwrap();
+    assert_eq!(book.best_ask(), None);
+    assert_eq!(book.get_order_status(2).unwrap().group.unwrap().role, GroupRole::TakeProfit);
+
+    book.place_order(create_test_order(5, dec!(100.0), dec!(1.0), Side::Ask, OrderType::Limit)).unwrap();
+    assert_eq!(book.get_order_status(1).unwrap().status, OrderStatus::Filled);
+    assert_eq!(book.best_ask(), Some(dec!(110.0)));
+
+    // the children are now one-cancels-other
+    book.place_order(create_test_order(6, dec!(110.0), dec!(2.0), Side::Bid, OrderType::Limit)).unwrap();
+    assert_eq!(book.get_order_status(2).unwrap().status, OrderStatus::Filled);
+    assert_eq!(book.get_order_status(3).unwrap().status, OrderStatus::Cancelled);
+}
+
+#[test]
+fn test_cancelling_bracket_parent_drops_children() {
+    let mut book = MatchingEngine::new();
+    let parent = create_test_order(1, dec!(100.0), dec!(2.0), Side::Bid, OrderType::Limit);
+    let take_profit = create_test_order(2, dec!(110.0), dec!(2.0), Side::Ask, OrderType::Limit);
+    let stop_loss = create_stop_order(3, dec!(90.0), dec!(0.0), dec!(2.0), Side::Ask, OrderType::Stop);
+    book.place_bracket(9, parent, take_profit, stop_loss).unwrap();
+
+    book.cancel_order(1).unwrap();
+    assert_eq!(book.get_order_status(2).unwrap().status, OrderStatus::Cancelled);
+    assert_eq!(book.get_order_status(3).unwrap().status, OrderStatus::Cancelled);
+    // and the group id is free again
+    assert!(book.place_oco(9, vec![]).is_ok());
+}
+
+#[test]
+fn test_hidden_orders_are_not_displayed_and_rank_behind() {
+    let mut book = MatchingEngine::new();
+    let mut hidden = create_test_order(1, dec!(100.0), dec!(5.0), Side::Ask, OrderType::Limit);
+    hidden.hidden = true;
+    book.place_order(hidden).unwrap();
+    let mut hidden_only = create_test_order(2, dec!(99.0), dec!(1.0), Side::Ask, OrderType::Limit);
+    hidden_only.hidden = true;
+    book.place_order(hidden_only).unwrap();
+    book.place_order(create_test_order(3, dec!(100.0), dec!(2.0), Side::Ask, OrderType::Limit)).unwrap();
+
+    assert_eq!(book.get_order_book(10).1, vec![(dec!(100.0), dec!(2.0))]);
+    assert_eq!(book.best_ask(), Some(dec!(100.0)));
+    let level: Vec<u64> = book.orders_at_price(dec!(100.0), Side::Ask).iter().map(|o| o.id).collect();
+    assert_eq!(level, vec![3, 1]);
+
+    // still tradable: the hidden 99 first, then the displayed order ahead of the hidden one
+    book.place_order(create_test_order(4, dec!(100.0), dec!(4.0), Side::Bid, OrderType::Limit)).unwrap();
+    let makers: Vec<u64> = book.get_trade_history(None).iter().map(|t| t.maker_order_id).collect();
+    assert_eq!(makers, vec![2, 3, 1]);
+    assert_eq!(book.get_order_status(1).unwrap().status, OrderStatus::PartiallyFilled);
+}
+
+#[test]
+fn test_midpoint_book_crosses_only_at_lit_mid() {
+    let mut book = MatchingEngine::new();
+    book.place_order(create_test_order(1, dec!(100.0), dec!(1.0), Side::Bid, OrderType::Limit)).unwrap();
+    book.place_order(create_test_order(2, dec!(102.0), dec!(1.0), Side::Ask, OrderType::Limit)).unwrap();
+
+    let mut dark_bid = create_test_order(3, dec!(100.5), dec!(5.0), Side::Bid, OrderType::Limit);
+    dark_bid.midpoint = true;
+    book.place_order(dark_bid).unwrap();
+    let mut dark_ask = create_test_order(4, dec!(0.0), dec!(3.0), Side::Ask, OrderType::Market);
+    dark_ask.midpoint = true;
+    book.place_order(dark_ask).unwrap();
+
+    // the mid is 101, through the dark bid's limit, and neither shows on the lit book
+    assert!(book.get_trade_history(None).is_empty());
+    assert_eq!(book.get_order_book(10), (vec![(dec!(100.0), dec!(1.0))], vec![(dec!(102.0), dec!(1.0))]));
+
+    // a lower lit offer moves the mid to 100.5 and the dark orders cross there
+    book.place_order(create_test_order(5, dec!(101.0), dec!(1.0), Side::Ask, OrderType::Limit)).unwrap();
+    let trades = book.get_trade_history(None);
+    assert_eq!(trades.len(), 1);
+    assert_eq!((trades[0].maker_order_id, trades[0].taker_order_id, trades[0].price, trades[0].quantity), (3, 4, dec!(100.5), dec!(3.0)));
+    assert_eq!(book.get_order_status(4).unwrap().status, OrderStatus::Filled);
+    assert_eq!(book.get_order_status(3).unwrap().remaining_quantity, dec!(2.0));
+
+    book.cancel_order(3).unwrap();
+    assert_eq!(book.get_order_status(3).unwrap().status, OrderStatus::Cancelled);
+}
+
+#[test]
+fn test_midpoint_orders_refuse_flags_the_dark_book_ignores() {
+    let mut book = MatchingEngine::new();
+    book.place_order(create_test_order(1, dec!(100.0), dec!(1.0), Side::Bid, OrderType::Limit)).unwrap();
+    book.place_order(create_test_order(2, dec!(102.0), dec!(1.0), Side::Ask, OrderType::Limit)).unwrap();
+    let mut resting = create_owned_order(3, 7, dec!(101.0), dec!(2.0), Side::Ask, None);
+    resting.midpoint = true;
+    book.place_order(resting).unwrap();
+
+    let mut aon = create_test_order(4, dec!(101.0), dec!(5.0), Side::Bid, OrderType::Limit);
+    aon.all_or_none = true;
+    let mut min_quantity = create_test_order(5, dec!(101.0), dec!(5.0), Side::Bid, OrderType::Limit);
+    min_quantity.min_quantity = Some(dec!(4.0));
+    let stp = create_owned_order(6, 7, dec!(101.0), dec!(1.0), Side::Bid, Some(StpMode::CancelNewest));
+    for mut order in [aon, min_quantity, stp] {
+        order.midpoint = true;
+        let id = order.id;
+        assert!(matches!(book.place_order(order), Err(EngineError::UnsupportedOrder(_))));
+        assert!(book.get_order_status(id).is_none());
+    }
+    // none of them got far enough to trade against the resting dark offer
+    assert!(book.get_trade_history(None).is_empty());
+}
+
+#[test]
+fn test_terminal_states_carry_a_reason() {
+    let mut book = MatchingEngine::new();
+    book.place_order(create_test_order(1, dec!(100.0), dec!(1.0), Side::Ask, OrderType::Limit)).unwrap();
+
+    let mut ioc = create_test_order(2, dec!(100.0), dec!(3.0), Side::Bid, OrderType::Limit);
+    ioc.time_in_force = TimeInForce::Ioc;
+    let ioc = book.place_order(ioc).unwrap();
+    assert_eq!((ioc.status, ioc.status_reason), (OrderStatus::Cancelled, Some(StatusReason::ImmediateOrCancel)));
+
+    let market = book.place_order(create_test_order(3, dec!(0.0), dec!(1.0), Side::Bid, OrderType::Market)).unwrap();
+    assert_eq!((market.status, market.status_reason), (OrderStatus::Cancelled, Some(StatusReason::NoLiquidity)));
+
+    let resting = book.place_order(create_test_order(4, dec!(99.0), dec!(1.0), Side::Bid, OrderType::Limit)).unwrap();
+    assert_eq!(resting.status_reason, None);
+    let cancelled = book.cancel_order(4).unwrap();
+    assert_eq!((cancelled.status, cancelled.status_reason), (OrderStatus::Cancelled, Some(StatusReason::CancelRequested)));
+    // and a dead order stays dead
+    assert_eq!(book.cancel_order(4), Err(EngineError::OrderClosed { order_id: 4, status: OrderStatus::Cancelled }));
+    assert!(OrderStatus::Rejected.is_terminal() && !OrderStatus::PartiallyFilled.is_terminal());
+}
+
+#[test]
+fn test_engine_errors_leave_the_book_untouched() {
+    let mut book = MatchingEngine::with_config(InstrumentConfig { max_order_quantity: Some(dec!(50.0)), ..Default::default() }).unwrap();
+    book.place_order(create_test_order(1, dec!(100.0), dec!(10.0), Side::Ask, OrderType::Limit)).unwrap();
+
+    assert_eq!(book.place_order(create_test_order(2, dec!(100.0), dec!(0.0), Side::Bid, OrderType::Limit)), Err(EngineError::InvalidQuantity(dec!(0.0))));
+    assert_eq!(book.place_order(create_test_order(3, dec!(-1.0), dec!(1.0), Side::Bid, OrderType::Limit)), Err(EngineError::InvalidPrice(dec!(-1.0))));
+    assert!(matches!(book.place_order(create_test_order(4, dec!(100.0), dec!(51.0), Side::Bid, OrderType::Limit)), Err(EngineError::RiskRejected(_))));
+    assert!(matches!(book.amend_order(1, None, Some(dec!(60.0)), 10), Err(EngineError::RiskRejected(_))));
+    assert!(book.get_order_status(2).is_none() && book.get_trade_history(None).is_empty());
+
+    assert_eq!(book.cancel_order(99), Err(EngineError::UnknownOrder(99)));
+    assert_eq!(book.amend_order(99, Some(dec!(101.0)), None, 10), Err(EngineError::UnknownOrder(99)));
+
+    // a bad leg keeps the whole group out
+    let bad_leg = create_test_order(6, dec!(0.0), dec!(1.0), Side::Bid, OrderType::Limit);
+    let good_leg = create_test_order(5, dec!(99.0), dec!(1.0), Side::Bid, OrderType::Limit);
+    assert_eq!(book.place_oco(1, vec![good_leg, bad_leg]), Err(EngineError::InvalidPrice(dec!(0.0))));
+    assert!(book.get_order_status(5).is_none());
+}
+
+#[test]
+fn test_halt_blocks_new_orders_but_not_cancels() {
+    let mut book = MatchingEngine::new();
+    book.place_order(create_test_order(1, dec!(100.0), dec!(5.0), Side::Ask, OrderType::Limit)).unwrap();
+    book.place_order(create_test_order(2, dec!(101.0), dec!(5.0), Side::Ask, OrderType::Limit)).unwrap();
+    book.halt();
+    assert_eq!(book.trading_phase(), TradingPhase::Halted);
+
+    let mut bid = create_test_order(3, dec!(100.0), dec!(5.0), Side::Bid, OrderType::Limit);
+    bid.instrument_id = 7;
+    assert_eq!(book.place_order(bid.clone()), Err(EngineError::InstrumentHalted(7)));
+    assert!(matches!(book.amend_order(1, Some(dec!(99.0)), None, 10), Err(EngineError::InstrumentHalted(_))));
+    assert_eq!(book.cancel_order(2).unwrap().status, OrderStatus::Cancelled);
+
+    book.resume();
+    assert_eq!(book.trading_phase(), TradingPhase::Continuous);
+    assert_eq!(book.place_order(bid).unwrap().status, OrderStatus::Filled);
+}
+
+#[test]
+fn test_resume_goes_back_to_the_auction_a_halt_interrupted() {
+    let mut book = MatchingEngine::new();
+    book.start_auction();
+    book.place_order(create_test_order(1, dec!(101.0), dec!(5.0), Side::Bid, OrderType::Limit)).unwrap();
+    book.place_order(create_test_order(2, dec!(100.0), dec!(5.0), Side::Ask, OrderType::Limit)).unwrap();
+    book.halt();
+    book.halt();
+
+    // still crossed, so it can't go straight to continuous matching
+    book.resume();
+    assert_eq!(book.trading_phase(), TradingPhase::Auction);
+    assert!(book.get_trade_history(None).is_empty());
+    assert_eq!(book.uncross().unwrap().volume, dec!(5.0));
+    assert_eq!(book.trading_phase(), TradingPhase::Continuous);
+}
+
+#[test]
+fn test_engine_rejects_live_duplicate_ids() {
+    let mut book = MatchingEngine::new();
+    book.place_order(create_test_order(1, dec!(100.0), dec!(5.0), Side::Bid, OrderType::Limit)).unwrap();
+    book.place_order(create_stop_order(2, dec!(110.0), dec!(0.0), dec!(1.0), Side::Bid, OrderType::Stop)).unwrap();
+
+    // resting or waiting on a trigger, the id is taken
+    assert_eq!(book.place_order(create_test_order(1, dec!(99.0), dec!(1.0), Side::Bid, OrderType::Limit)), Err(EngineError::DuplicateOrderId(1)));
+    assert_eq!(book.place_order(create_test_order(2, dec!(99.0), dec!(1.0), Side::Bid, OrderType::Limit)), Err(EngineError::DuplicateOrderId(2)));
+    assert_eq!(book.get_order_book(10).0, vec![(dec!(100.0), dec!(5.0))]);
+
+    // and by default stays taken once the order is done
+    book.cancel_order(1).unwrap();
+    assert_eq!(book.place_order(create_test_order(1, dec!(99.0), dec!(1.0), Side::Bid, OrderType::Limit)), Err(EngineError::DuplicateOrderId(1)));
+
+    // legs of one group can't share an id either
+    let legs = vec![
+        create_test_order(3, dec!(105.0), dec!(1.0), Side::Ask, OrderType::Limit),
+        create_test_order(3, dec!(106.0), dec!(1.0), Side::Ask, OrderType::Limit),
+    ];
+    assert_eq!(book.place_oco(1, legs), Err(EngineError::DuplicateOrderId(3)));
+    assert!(book.get_order_status(3).is_none());
+}
+
+#[test]
+fn test_terminal_ids_can_be_reused_when_configured() {
+    let mut book = MatchingEngine::with_config(InstrumentConfig { id_reuse: IdReuse::AfterTerminal, ..Default::default() }).unwrap();
+    let now = Utc::now();
+    let mut gtd = create_test_order(1, dec!(100.0), dec!(5.0), Side::Bid, OrderType::Limit);
+    gtd.time_in_force = TimeInForce::Gtd;
+    gtd.expire_time = Some(now + Duration::minutes(1));
+    book.place_order(gtd).unwrap();
+    assert_eq!(book.place_order(create_test_order(1, dec!(99.0), dec!(1.0), Side::Bid, OrderType::Limit)), Err(EngineError::DuplicateOrderId(1)));
+    book.place_order(create_test_order(2, dec!(100.0), dec!(2.0), Side::Ask, OrderType::Limit)).unwrap();
+
+    book.cancel_order(1).unwrap();
+    let reused = book.place_order(create_test_order(1, dec!(99.0), dec!(2.0), Side::Bid, OrderType::Limit)).unwrap();
+    assert_eq!(reused.status, OrderStatus::Pending);
+    assert_eq!(book.get_order_book(10).0, vec![(dec!(99.0), dec!(2.0))]);
+
+    // the old order's expiry doesn't carry over to the new one, and nor do its trades
+    assert!(book.expire_orders(now + Duration::minutes(2)).is_empty());
+    assert_eq!(book.get_order_status(1).unwrap().status, OrderStatus::Pending);
+    assert!(book.get_order_trades(1).is_empty());
+    book.place_order(create_test_order(3, dec!(99.0), dec!(1.0), Side::Ask, OrderType::Limit)).unwrap();
+    let trades: Vec<_> = book.get_order_trades(1).iter().map(|trade| (trade.trade_id, trade.maker_order_id)).collect();
+    assert_eq!(trades, vec![(2, 1)]);
+}
+
+#[test]
+fn test_reused_ids_leave_the_old_order_group() {
+    let mut book = MatchingEngine::with_config(InstrumentConfig { id_reuse: IdReuse::AfterTerminal, ..Default::default() }).unwrap();
+    // an IOC leg with nothing to trade against is killed, and its sibling keeps working
+    let mut ioc = create_test_order(1, dec!(100.0), dec!(1.0), Side::Bid, OrderType::Limit);
+    ioc.time_in_force = TimeInForce::Ioc;
+    let resting = create_test_order(2, dec!(105.0), dec!(1.0), Side::Ask, OrderType::Limit);
+    book.place_oco(7, vec![ioc, resting]).unwrap();
+    assert_eq!(book.get_order_status(1).unwrap().status, OrderStatus::Cancelled);
+
+    let reused = book.place_order(create_test_order(1, dec!(99.0), dec!(1.0), Side::Bid, OrderType::Limit)).unwrap();
+    assert_eq!(reused.group, None);
+    book.place_order(create_test_order(3, dec!(105.0), dec!(1.0), Side::Bid, OrderType::Limit)).unwrap();
+    assert_eq!(book.get_order_status(2).unwrap().status, OrderStatus::Filled);
+    assert_eq!(book.get_order_status(1).unwrap().status, OrderStatus::Pending);
+    assert_eq!(book.get_order_book(10).0, vec![(dec!(99.0), dec!(1.0))]);
+}
+
+#[test]
+fn test_resting_order_state_matches_the_level() {
+    let mut book = MatchingEngine::new();
+    let mut iceberg = create_test_order(1, dec!(100.0), dec!(10.0), Side::Ask, OrderType::Limit);
+    iceberg.display_quantity = Some(dec!(4.0));
+    book.place_order(iceberg).unwrap();
+    book.place_order(create_owned_order(2, 7, dec!(100.0), dec!(5.0), Side::Ask, None)).unwrap();
+
+    // a partial maker fill, an iceberg replenish and an STP decrement all show up
+    // the same way whichever way the order is looked at
+    book.place_order(create_test_order(3, dec!(100.0), dec!(5.0), Side::Bid, OrderType::Limit)).unwrap();
+    book.place_order(create_owned_order(4, 7, dec!(100.0), dec!(2.0), Side::Bid, Some(StpMode::DecrementAndCancel))).unwrap();
+
+    let level = book.orders_at_price(dec!(100.0), Side::Ask);
+    assert_eq!(level.iter().map(|o| o.id).collect::<Vec<_>>(), vec![2, 1]);
+    for order in &level {
+        assert_eq!(book.get_order_status(order.id).as_ref(), Some(order));
+    }
+    let iceberg = book.get_order_status(1).unwrap();
+    assert_eq!((iceberg.status, iceberg.remaining_quantity, iceberg.visible_quantity()), (OrderStatus::PartiallyFilled, dec!(6.0), dec!(4.0)));
+    let decremented = book.get_order_status(2).unwrap();
+    assert_eq!((decremented.quantity, decremented.remaining_quantity), (dec!(3.0), dec!(2.0)));
+}
+
+#[test]
+fn test_cancel_from_the_middle_of_a_level_keeps_queue_and_totals() {
+    let mut book = MatchingEngine::new();
+    for id in 1..=4 {
+        book.place_order(create_test_order(id, dec!(100.0), dec!(2.0), Side::Ask, OrderType::Limit)).unwrap();
+    }
+    let mut hidden = create_test_order(5, dec!(100.0), dec!(3.0), Side::Ask, OrderType::Limit);
+    hidden.hidden = true;
+    book.place_order(hidden).unwrap();
+    book.place_order(create_test_order(6, dec!(100.0), dec!(1.0), Side::Ask, OrderType::Limit)).unwrap();
+
+    book.cancel_order(2).unwrap();
+    book.cancel_order(3).unwrap();
+    book.amend_order(4, None, Some(dec!(1.5)), 10).unwrap();
+    let ids = |book: &MatchingEngine| book.orders_at_price(dec!(100.0), Side::Ask).iter().map(|o| o.id).collect::<Vec<_>>();
+    assert_eq!(ids(&book), vec![1, 4, 6, 5]);
+    assert_eq!(book.get_order_book(1).1, vec![(dec!(100.0), dec!(4.5))]);
+
+    // the taker walks the shortened queue, hidden order last
+    book.place_order(create_test_order(7, dec!(100.0), dec!(5.0), Side::Bid, OrderType::Limit)).unwrap();
+    let makers: Vec<u64> = book.get_trade_history(None).iter().map(|t| t.maker_order_id).collect();
+    assert_eq!(makers, vec![1, 4, 6, 5]);
+    assert_eq!(ids(&book), vec![5]);
+    assert_eq!(book.get_order_book(1).1, vec![]);
+
+    book.cancel_order(5).unwrap();
+    assert!(book.orders_at_price(dec!(100.0), Side::Ask).is_empty());
+    assert_eq!(book.best_ask(), None);
+}
+
+#[test]
+fn test_prices_and_quantities_finer_than_the_scale_are_rejected() {
+    let mut book = MatchingEngine::with_config(InstrumentConfig { price_scale: 2, quantity_scale: 0, ..Default::default() }).unwrap();
+    assert_eq!(
+        book.place_order(create_test_order(1, dec!(100.005), dec!(1), Side::Bid, OrderType::Limit)),
+        Err(EngineError::InvalidPrice(dec!(100.005)))
+    );
+    assert_eq!(
+        book.place_order(create_test_order(1, dec!(100.00), dec!(1.5), Side::Bid, OrderType::Limit)),
+        Err(EngineError::InvalidQuantity(dec!(1.5)))
+    );
+    assert!(book.get_order_status(1).is_none());
+
+    // trailing zeros past the scale are fine, and what comes back is the same value
+    let placed = book.place_order(create_test_order(1, dec!(100.0100), dec!(2.000), Side::Bid, OrderType::Limit)).unwrap();
+    assert_eq!((placed.price, placed.quantity), (dec!(100.01), dec!(2)));
+    assert_eq!(book.amend_order(1, Some(dec!(100.011)), None, 2), Err(EngineError::InvalidPrice(dec!(100.011))));
+    assert_eq!(book.get_order_book(1).0, vec![(dec!(100.01), dec!(2))]);
+}
+
+#[test]
+fn test_midpoint_rounds_down_to_the_price_scale() {
+    let mut book = MatchingEngine::with_config(InstrumentConfig { price_scale: 2, ..Default::default() }).unwrap();
+    book.place_order(create_test_order(1, dec!(100.00), dec!(1.0), Side::Bid, OrderType::Limit)).unwrap();
+    book.place_order(create_test_order(2, dec!(100.01), dec!(1.0), Side::Ask, OrderType::Limit)).unwrap();
+
+    let mut dark_bid = create_test_order(3, dec!(100.01), dec!(1.0), Side::Bid, OrderType::Limit);
+    dark_bid.midpoint = true;
+    book.place_order(dark_bid).unwrap();
+    let mut dark_ask = create_test_order(4, dec!(100.00), dec!(1.0), Side::Ask, OrderType::Limit);
+    dark_ask.midpoint = true;
+    book.place_order(dark_ask).unwrap();
+
+    // a one-tick spread has no mid at this scale, so the cross happens a half tick down
+    let trades = book.get_trade_history(None);
+    assert_eq!((trades.len(), trades[0].price), (1, dec!(100.00)));
+}
+
+#[test]
+fn test_scales_that_overflow_are_refused() {
+    for config in [
+        InstrumentConfig { price_scale: 19, ..Default::default() },
+        InstrumentConfig { quantity_scale: 29, ..Default::default() },
+        // scales in range, but settings too big to count in ticks or lots at them
+        InstrumentConfig { price_scale: 18, tick_size: dec!(100), ..Default::default() },
+        InstrumentConfig { max_order_quantity: Some(dec!(1000000000000)), ..Default::default() },
+    ] {
+        assert!(matches!(MatchingEngine::with_config(config), Err(EngineError::InvalidConfig(_))));
+    }
+
+    // the finest scale there is room for still trades
+    let mut book = MatchingEngine::with_config(InstrumentConfig { price_scale: 18, tick_size: dec!(0.01), ..Default::default() }).unwrap();
+    book.place_order(create_test_order(1, dec!(1.5), dec!(1), Side::Ask, OrderType::Limit)).unwrap();
+    book.place_order(create_test_order(2, dec!(1.5), dec!(1), Side::Bid, OrderType::Limit)).unwrap();
+    assert_eq!(book.get_trade_history(None)[0].price, dec!(1.5));
+}
+
+#[test]
+fn test_totals_past_a_lot_count_at_the_finest_quantity_scale() {
+    // at 10^18 lots a unit, an i64 holds a little over 9 units and an order at most half that
+    let mut book = MatchingEngine::with_config(InstrumentConfig { quantity_scale: 18, ..Default::default() }).unwrap();
+    assert_eq!(
+        book.place_order(create_test_order(1, dec!(100), dec!(5), Side::Bid, OrderType::Limit)),
+        Err(EngineError::InvalidQuantity(dec!(5)))
+    );
+
+    book.start_auction();
+    for id in 1..=3 {
+        book.place_order(create_test_order(id, dec!(100), dec!(4), Side::Bid, OrderType::Limit)).unwrap();
+        book.place_order(create_test_order(10 + id, dec!(100), dec!(4), Side::Ask, OrderType::Limit)).unwrap();
+    }
+    assert_eq!(book.get_order_book(1), (vec![(dec!(100), dec!(12))], vec![(dec!(100), dec!(12))]));
+    let uncross = book.uncross().unwrap();
+    assert_eq!((uncross.volume, uncross.imbalance), (dec!(12), dec!(0)));
+
+    // a fill-or-kill check sums several resting orders before it's covered
+    for id in 1..=3 {
+        book.place_order(create_test_order(20 + id, dec!(100), dec!(4), Side::Bid, OrderType::Limit)).unwrap();
+    }
+    let mut fok = create_test_order(30, dec!(100), dec!(4.5), Side::Ask, OrderType::Limit);
+    fok.time_in_force = TimeInForce::Fok;
+    assert_eq!(book.place_order(fok).unwrap().status, OrderStatus::Filled);
+    assert_eq!(book.get_order_book(1).0, vec![(dec!(100), dec!(7.5))]);
+}
+
+fn dense_ladder_engine(ticks: usize) -> MatchingEngine {
+    MatchingEngine::with_config(InstrumentConfig {
+        tick_size: dec!(0.01),
+        price_ladder: PriceLadder::Dense { reference_price: dec!(100.00), ticks },
+        ..Default::default()
+    })
+    .unwrap()
+}
+
+#[test]
+fn test_dense_ladder_books_and_trades_like_the_tree() {
+    let mut tree = MatchingEngine::with_config(InstrumentConfig { tick_size: dec!(0.01), ..Default::default() }).unwrap();
+    // a window narrower than the flow, so some prices land off it
+    let mut dense = dense_ladder_engine(40);
+
+    let mut seed = 7u64;
+    let mut next = |n: u64| {
+        seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        (seed >> 33) % n
+    };
+    for id in 1..=2000u64 {
+        if id % 5 == 0 {
+            let victim = 1 + next(id);
+            assert_eq!(tree.cancel_order(victim), dense.cancel_order(victim));
+            continue;
+        }
+        let side = if next(2) == 0 { Side::Bid } else { Side::Ask };
+        // mostly whole ticks around 100, now and then half a tick off the grid
+        let ticks = next(61) as i64 - 30;
+        let off_grid = if next(10) == 0 { dec!(0.005) } else { dec!(0) };
+        let price = dec!(100.00) + rust_decimal::Decimal::new(ticks, 2) + off_grid;
+        let quantity = rust_decimal::Decimal::from(1 + next(5));
+        let order = create_test_order(id, price, quantity, side, OrderType::Limit);
+        assert_eq!(tree.place_order(order.clone()), dense.place_order(order));
+        assert_eq!(tree.get_order_book(5), dense.get_order_book(5));
+    }
+    assert_eq!(tree.get_order_book(usize::MAX), dense.get_order_book(usize::MAX));
+    assert_eq!(tree.get_trade_history(None), dense.get_trade_history(None));
+}
+
+#[test]
+fn test_dense_ladder_touch_moves_past_emptied_levels() {
+    let mut book = dense_ladder_engine(10);
+    book.place_order(create_test_order(1, dec!(100.00), dec!(1.0), Side::Bid, OrderType::Limit)).unwrap();
+    book.place_order(create_test_order(2, dec!(99.97), dec!(1.0), Side::Bid, OrderType::Limit)).unwrap();
+    // above the window
+    book.place_order(create_test_order(3, dec!(101.00), dec!(1.0), Side::Ask, OrderType::Limit)).unwrap();
+    assert_eq!((book.best_bid(), book.best_ask()), (Some(dec!(100.00)), Some(dec!(101.00))));
+
+    book.cancel_order(1).unwrap();
+    assert_eq!(book.best_bid(), Some(dec!(99.97)));
+
+    // a bid through the ask takes it and rests above the window, ahead of the one inside
+    book.place_order(create_test_order(4, dec!(101.00), dec!(2.0), Side::Bid, OrderType::Limit)).unwrap();
+    assert_eq!(book.best_ask(), None);
+    assert_eq!(book.get_order_book(5).0, vec![(dec!(101.00), dec!(1.0)), (dec!(99.97), dec!(1.0))]);
+
+    book.cancel_order(2).unwrap();
+    book.cancel_order(4).unwrap();
+    assert_eq!(book.get_order_book(5), (vec![], vec![]));
+}
+
+#[test]
+fn test_apply_reports_fills_rests_and_level_changes_in_order() {
+    let commands = vec![
+        Command::Place(create_test_order(1, dec!(100.0), dec!(2.0), Side::Ask, OrderType::Limit)),
+        Command::Place(create_test_order(2, dec!(100.0), dec!(3.0), Side::Bid, OrderType::Limit)),
+    ];
+    let mut book = MatchingEngine::new();
+    let events: Vec<Vec<EngineEvent>> = commands.iter().cloned().map(|command| book.apply(command)).collect();
+
+    assert!(matches!(&events[0][..], [EngineEvent::OrderAccepted(a), EngineEvent::OrderRested(r), _] if a.id == 1 && r.id == 1));
+    assert_eq!(events[0][2], EngineEvent::BookLevelChanged { side: Side::Ask, price: dec!(100.0), quantity: dec!(2.0) });
+
+    let [EngineEvent::OrderAccepted(accepted), EngineEvent::Fill(maker), EngineEvent::Fill(taker), EngineEvent::OrderRested(rested), asks, bids] =
+        &events[1][..]
+    else {
+        panic!("unexpected events {:?}", events[1]);
+    };
+    assert_eq!(accepted.id, 2);
+    assert_eq!(
+        *maker,
+        Fill { trade_id: 1, execution_id: 1, order_id: 1, counterparty_order_id: 2, side: Side::Ask, liquidity: Liquidity::Maker, price: dec!(100.0), quantity: dec!(2.0) }
+    );
+    assert_eq!((taker.order_id, taker.side, taker.liquidity), (2, Side::Bid, Liquidity::Taker));
+    assert_eq!((rested.id, rested.remaining_quantity), (2, dec!(1.0)));
+    assert_eq!(*asks, EngineEvent::BookLevelChanged { side: Side::Ask, price: dec!(100.0), quantity: dec!(0) });
+    assert_eq!(*bids, EngineEvent::BookLevelChanged { side: Side::Bid, price: dec!(100.0), quantity: dec!(1.0) });
+
+    // the same commands give the same events
+    let mut replay = MatchingEngine::new();
+    let replayed: Vec<Vec<EngineEvent>> = commands.into_iter().map(|command| replay.apply(command)).collect();
+    assert_eq!(events, replayed);
+}
+
+#[test]
+fn test_apply_reports_cancels_expiries_and_refusals() {
+    let mut book = MatchingEngine::new();
+    assert_eq!(
+        book.apply(Command::Cancel { order_id: 9 }),
+        vec![EngineEvent::CommandRejected { order_id: 9, error: EngineError::UnknownOrder(9) }]
+    );
+
+    // a hidden order changes nothing anyone can see
+    let mut hidden = create_test_order(1, dec!(99.0), dec!(5.0), Side::Bid, OrderType::Limit);
+    hidden.hidden = true;
+    let events = book.apply(Command::Place(hidden));
+    assert!(matches!(&events[..], [EngineEvent::OrderAccepted(_), EngineEvent::OrderRested(_)]));
+
+    let now = Utc::now();
+    let mut gtd = create_test_order(2, dec!(98.0), dec!(5.0), Side::Bid, OrderType::Limit);
+    gtd.time_in_force = TimeInForce::Gtd;
+    gtd.expire_time = Some(now);
+    book.apply(Command::Place(gtd));
+    let events = book.apply(Command::ExpireOrders { now });
+    assert!(matches!(&events[0], EngineEvent::OrderExpired(order) if order.id == 2));
+    assert_eq!(events[1], EngineEvent::BookLevelChanged { side: Side::Bid, price: dec!(98.0), quantity: dec!(0) });
+
+    let events = book.apply(Command::Cancel { order_id: 1 });
+    assert!(matches!(&events[..], [EngineEvent::OrderCancelled(order)] if order.status_reason == Some(StatusReason::CancelRequested)));
+
+    // a stop waits off the book until it's elected
+    let events = book.apply(Command::Place(create_stop_order(3, dec!(95.0), dec!(0.0), dec!(1.0), Side::Ask, OrderType::Stop)));
+    assert!(matches!(&events[..], [EngineEvent::OrderAccepted(_), EngineEvent::OrderParked(order)] if order.id == 3));
+}
+
+#[test]
+fn test_apply_reports_phase_changes_uncrosses_and_groups() {
+    let mut book = MatchingEngine::new();
+    assert_eq!(book.apply(Command::StartAuction), vec![EngineEvent::PhaseChanged(TradingPhase::Auction)]);
+    book.apply(Command::Place(create_test_order(1, dec!(101.0), dec!(5.0), Side::Bid, OrderType::Limit)));
+    book.apply(Command::Place(create_test_order(2, dec!(100.0), dec!(5.0), Side::Ask, OrderType::Limit)));
+    assert_eq!(book.apply(Command::Halt), vec![EngineEvent::PhaseChanged(TradingPhase::Halted)]);
+    assert_eq!(book.apply(Command::Resume), vec![EngineEvent::PhaseChanged(TradingPhase::Auction)]);
+
+    let events = book.apply(Command::Uncross);
+    let [EngineEvent::Uncrossed(uncross), EngineEvent::Fill(_), EngineEvent::Fill(_), EngineEvent::PhaseChanged(phase), ..] = &events[..] else {
+        panic!("unexpected events {events:?}");
+    };
+    assert_eq!((uncross.price, uncross.volume), (dec!(100.0), dec!(5.0)));
+    assert_eq!(*phase, TradingPhase::Continuous);
+
+    let legs = vec![
+        create_test_order(3, dec!(105.0), dec!(1.0), Side::Ask, OrderType::Limit),
+        create_test_order(4, dec!(95.0), dec!(1.0), Side::Ask, OrderType::Limit),
+    ];
+    let events = book.apply(Command::PlaceOco { group_id: 1, legs: legs.clone() });
+    assert!(matches!(&events[..2], [EngineEvent::OrderAccepted(a), EngineEvent::OrderAccepted(b)] if (a.id, b.id) == (3, 4)));
+    assert_eq!(
+        book.apply(Command::PlaceOco { group_id: 1, legs }),
+        vec![EngineEvent::CommandRejected { order_id: 3, error: EngineError::DuplicateGroupId(1) }]
+    );
+}
+
+#[test]
+fn test_trade_ids_count_up_and_can_be_looked_up() {
+    let mut book = MatchingEngine::new();
+    book.place_order(create_test_order(1, dec!(100.0), dec!(10.0), Side::Bid, OrderType::Limit)).unwrap();
+    book.place_order(create_test_order(2, dec!(101.0), dec!(10.0), Side::Bid, OrderType::Limit)).unwrap();
+    book.place_order(create_test_order(3, dec!(100.0), dec!(15.0), Side::Ask, OrderType::Limit)).unwrap();
+    book.place_order(create_test_order(4, dec!(100.0), dec!(2.0), Side::Ask, OrderType::Limit)).unwrap();
+
+    let ids: Vec<_> = book
+        .get_trade_history(None)
+        .iter()
+        .map(|trade| (trade.trade_id, trade.maker_execution_id, trade.taker_execution_id))
+        .collect();
+    assert_eq!(ids, vec![(1, 1, 2), (2, 3, 4), (3, 5, 6)]);
+
+    let second = book.get_trade(2).unwrap();
+    assert_eq!((second.maker_order_id, second.taker_order_id, second.quantity), (1, 3, dec!(5.0)));
+    assert_eq!(book.get_trade(4), None);
+
+    let by_order = |order_id| book.get_order_trades(order_id).iter().map(|trade| trade.trade_id).collect::<Vec<_>>();
+    assert_eq!(by_order(1), vec![2, 3]);
+    assert_eq!(by_order(3), vec![1, 2]);
+    assert_eq!(by_order(9), Vec::<u64>::new());
+}
+
+#[test]
+fn test_fills_carry_their_trade_and_execution_ids() {
+    let mut book = MatchingEngine::new();
+    book.apply(Command::Place(create_test_order(1, dec!(100.0), dec!(1.0), Side::Ask, OrderType::Limit)));
+    book.apply(Command::Place(create_test_order(2, dec!(100.0), dec!(1.0), Side::Ask, OrderType::Limit)));
+
+    let events = book.apply(Command::Place(create_test_order(3, dec!(100.0), dec!(2.0), Side::Bid, OrderType::Limit)));
+    let fills: Vec<_> = events
+        .iter()
+        .filter_map(|event| match event {
+            EngineEvent::Fill(fill) => Some((fill.trade_id, fill.execution_id, fill.order_id)),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(fills, vec![(1, 1, 1), (1, 2, 3), (2, 3, 2), (2, 4, 3)]);
+}
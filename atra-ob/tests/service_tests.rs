@@ -0,0 +1,182 @@
+use atra_ob::api::service::{OrderBookService, SequencerConfig};
+use atra_ob::core::{EngineEvent, IdReuse, InstrumentConfig};
+use atra_ob::proto::order_book_service_server::OrderBookService as _;
+use atra_ob::proto::{AmendOrderRequest, CancelOrderRequest, DecimalValue, ErrorCode, ErrorDetail, GetTradeRequest, Liquidity, OrderRequest, OrderStatus, OrderType, Side, StpMode, TimeInForce};
+use chrono::Utc;
+use prost::Message;
+use prost_types::Timestamp;
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use tonic::{Code, Request};
+
+fn limit_order(id: u64, instrument_id: u32, units: i64) -> OrderRequest {
+    OrderRequest {
+        id,
+        instrument_id,
+        price: Some(DecimalValue { units, scale: 0 }),
+        quantity: Some(DecimalValue { units: 1, scale: 0 }),
+        side: Side::Bid as i32,
+        order_type: OrderType::Limit as i32,
+        ..Default::default()
+    }
+}
+
+fn decimal(value: &Option<DecimalValue>) -> Decimal {
+    let value = value.as_ref().unwrap();
+    Decimal::new(value.units, value.scale as u32)
+}
+
+fn service() -> OrderBookService {
+    OrderBookService::new(1, SequencerConfig { strict_sequence_validation: false })
+        .with_instrument_config(2, InstrumentConfig { id_reuse: IdReuse::AfterTerminal, ..Default::default() })
+}
+
+#[tokio::test]
+async fn test_duplicate_order_id_is_already_exists() {
+    let service = service();
+    service.place_order(Request::new(limit_order(1, 1, 100))).await.unwrap();
+
+    let err = service.place_order(Request::new(limit_order(1, 1, 99))).await.unwrap_err();
+    assert_eq!(err.code(), Code::AlreadyExists);
+    let detail = ErrorDetail::decode(err.details()).unwrap();
+    assert_eq!(detail.code, ErrorCode::DuplicateOrderId as i32);
+}
+
+#[tokio::test]
+async fn test_terminal_id_reuse_follows_instrument_config() {
+    let service = service();
+    for instrument_id in [1, 2] {
+        service.place_order(Request::new(limit_order(7, instrument_id, 100))).await.unwrap();
+        service
+            .cancel_order(Request::new(CancelOrderRequest { order_id: 7, instrument_id, ..Default::default() }))
+            .await
+            .unwrap();
+    }
+
+    let err = service.place_order(Request::new(limit_order(7, 1, 101))).await.unwrap_err();
+    assert_eq!(err.code(), Code::AlreadyExists);
+    let reused = service.place_order(Request::new(limit_order(7, 2, 101))).await.unwrap().into_inner();
+    assert_eq!(reused.status, OrderStatus::Pending as i32);
+}
+
+#[tokio::test]
+async fn test_place_order_returns_its_fills() {
+    let service = service();
+    for (id, units) in [(1, 100), (2, 101)] {
+        let ask = OrderRequest { side: Side::Ask as i32, ..limit_order(id, 1, units) };
+        let resting = service.place_order(Request::new(ask)).await.unwrap().into_inner();
+        assert!(resting.fills.is_empty());
+        assert_eq!(resting.average_fill_price, None);
+    }
+
+    let bid = OrderRequest { quantity: Some(DecimalValue { units: 3, scale: 0 }), ..limit_order(3, 1, 101) };
+    let placed = service.place_order(Request::new(bid)).await.unwrap().into_inner();
+    assert_eq!(placed.status, OrderStatus::PartiallyFilled as i32);
+    let fills: Vec<_> = placed
+        .fills
+        .iter()
+        .map(|fill| (fill.trade_id, decimal(&fill.price), fill.maker_order_id, fill.liquidity))
+        .collect();
+    assert_eq!(fills, vec![(1, dec!(100), 1, Liquidity::Taker as i32), (2, dec!(101), 2, Liquidity::Taker as i32)]);
+    assert_eq!(decimal(&placed.filled_quantity), dec!(2));
+    assert_eq!(decimal(&placed.average_fill_price), dec!(100.5));
+}
+
+#[tokio::test]
+async fn test_average_fill_price_is_rounded_to_the_price_scale() {
+    let config = InstrumentConfig { price_scale: 2, tick_size: dec!(0.01), ..Default::default() };
+    let service = service().with_instrument_config(3, config);
+    let ask = OrderRequest { side: Side::Ask as i32, ..limit_order(1, 3, 100) };
+    service.place_order(Request::new(ask)).await.unwrap();
+    let ask = OrderRequest { side: Side::Ask as i32, quantity: Some(DecimalValue { units: 2, scale: 0 }), ..limit_order(2, 3, 101) };
+    service.place_order(Request::new(ask)).await.unwrap();
+
+    let bid = OrderRequest { quantity: Some(DecimalValue { units: 3, scale: 0 }), ..limit_order(3, 3, 101) };
+    let placed = service.place_order(Request::new(bid)).await.unwrap().into_inner();
+    assert_eq!(decimal(&placed.average_fill_price), dec!(100.67));
+}
+
+#[tokio::test]
+async fn test_get_trade_by_id() {
+    let service = service();
+    let ask = OrderRequest { side: Side::Ask as i32, ..limit_order(1, 1, 100) };
+    service.place_order(Request::new(ask)).await.unwrap();
+    let placed = service.place_order(Request::new(limit_order(2, 1, 100))).await.unwrap().into_inner();
+    assert_eq!(placed.fills[0].execution_id, 2);
+
+    let trade = service
+        .get_trade(Request::new(GetTradeRequest { trade_id: 1, instrument_id: 1 }))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!((trade.trade_id, trade.maker_order_id, trade.taker_order_id), (1, 1, 2));
+    assert_eq!((trade.maker_execution_id, trade.taker_execution_id), (1, 2));
+
+    let err = service
+        .get_trade(Request::new(GetTradeRequest { trade_id: 2, instrument_id: 1 }))
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Code::NotFound);
+    assert_eq!(ErrorDetail::decode(err.details()).unwrap().code, ErrorCode::UnknownTrade as i32);
+}
+
+#[tokio::test]
+async fn test_amend_in_place_keeps_the_sequence() {
+    let service = OrderBookService::new(1, SequencerConfig { strict_sequence_validation: true });
+    let first = OrderRequest { sequence_number: 1, quantity: Some(DecimalValue { units: 5, scale: 0 }), ..limit_order(1, 1, 100) };
+    service.place_order(Request::new(first)).await.unwrap();
+
+    let amend = AmendOrderRequest {
+        order_id: 1,
+        instrument_id: 1,
+        quantity: Some(DecimalValue { units: 3, scale: 0 }),
+        ..Default::default()
+    };
+    service.amend_order(Request::new(amend)).await.unwrap();
+    let amend = AmendOrderRequest { order_id: 1, instrument_id: 1, price: Some(DecimalValue { units: 99, scale: 0 }), ..Default::default() };
+    service.amend_order(Request::new(amend)).await.unwrap();
+
+    // the shrink kept its place, the reprice took sequence 2
+    let second = OrderRequest { sequence_number: 3, ..limit_order(2, 1, 98) };
+    service.place_order(Request::new(second)).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_midpoint_orders_refuse_all_or_none_min_quantity_and_stp() {
+    let service = service();
+    let midpoint = OrderRequest { midpoint: true, owner_id: Some(7), ..limit_order(1, 1, 100) };
+    for request in [
+        OrderRequest { all_or_none: true, ..midpoint.clone() },
+        OrderRequest { min_quantity: Some(DecimalValue { units: 1, scale: 0 }), ..midpoint.clone() },
+        OrderRequest { stp_mode: StpMode::CancelNewest as i32, ..midpoint.clone() },
+    ] {
+        let err = service.place_order(Request::new(request)).await.unwrap_err();
+        assert_eq!(err.code(), Code::InvalidArgument);
+    }
+    service.place_order(Request::new(midpoint)).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_expiries_are_published_without_a_command() {
+    let service = service();
+    let mut events = service.subscribe_events();
+    let expire_time = Utc::now() + chrono::Duration::milliseconds(200);
+    let gtd = OrderRequest {
+        time_in_force: TimeInForce::Gtd as i32,
+        expire_time: Some(Timestamp { seconds: expire_time.timestamp(), nanos: expire_time.timestamp_subsec_nanos() as i32 }),
+        ..limit_order(1, 1, 100)
+    };
+    service.place_order(Request::new(gtd)).await.unwrap();
+
+    // nothing else reaches the lane, so the worker has to wake for the expiry on its own
+    let expired = tokio::time::timeout(std::time::Duration::from_secs(5), async {
+        loop {
+            if let (1, EngineEvent::OrderExpired(order)) = events.recv().await.unwrap() {
+                break order;
+            }
+        }
+    })
+    .await
+    .unwrap();
+    assert_eq!(expired.id, 1);
+}